@@ -0,0 +1,96 @@
+use std::{path::PathBuf, sync::Arc, time::Duration};
+
+use common_types::{IMAGE_HEIGHT, IMAGE_WIDTH};
+use drivers::{Simulator, LED_COUNT, SAMPLE_RATE};
+use image::{Rgb, RgbImage};
+use rgb565::Rgb565;
+
+// recording runs at 30 fps, and saving every frame of it isn't useful
+const SNAPSHOT_INTERVAL: Duration = Duration::from_millis(250);
+
+// saves the screen whenever it changes, as numbered pngs with the text alongside
+pub async fn snapshot_loop(sim: Arc<Simulator>, dir: PathBuf) -> anyhow::Result<()> {
+    let mut interval = tokio::time::interval(SNAPSHOT_INTERVAL);
+    let mut count = 0;
+    let mut last_pixels = vec![];
+    let mut last_text = vec![];
+    let mut last_hex = [0; 6];
+    let mut last_leds = [false; LED_COUNT];
+
+    loop {
+        interval.tick().await;
+
+        let hex = sim.hex();
+        if hex != last_hex {
+            println!(
+                "HEX: {}",
+                hex.map(segments_to_char).iter().collect::<String>()
+            );
+            last_hex = hex;
+        }
+
+        let leds = sim.leds();
+        if leds != last_leds {
+            println!(
+                "LEDs: {}",
+                leds.iter()
+                    .rev()
+                    .map(|&on| if on { '*' } else { '.' })
+                    .collect::<String>()
+            );
+            last_leds = leds;
+        }
+
+        let audio = sim.take_audio();
+        if !audio.is_empty() {
+            println!(
+                "Audio: {} ms",
+                audio.len() as u64 * 1000 / SAMPLE_RATE as u64
+            );
+        }
+
+        let pixels = sim.front_buffer();
+        let text = sim.text();
+        if pixels == last_pixels && text == last_text {
+            continue;
+        }
+
+        let img = RgbImage::from_fn(IMAGE_WIDTH as u32, IMAGE_HEIGHT as u32, |x, y| {
+            let pixel = pixels[y as usize * IMAGE_WIDTH + x as usize];
+            Rgb(Rgb565::from_rgb565_le(pixel.to_le_bytes()).to_rgb888_components())
+        });
+        img.save(dir.join(format!("{count:05}.png")))?;
+
+        // the character buffer is drawn over the pixels on a real display
+        let lines: Vec<_> = text
+            .iter()
+            .map(|row| row.trim_end())
+            .filter(|row| !row.is_empty())
+            .collect();
+        if !lines.is_empty() {
+            std::fs::write(dir.join(format!("{count:05}.txt")), lines.join("\n"))?;
+        }
+
+        count += 1;
+        last_pixels = pixels;
+        last_text = text;
+    }
+}
+
+// the inverse of `HexDisplay::digit_to_hex`
+fn segments_to_char(segments: u8) -> char {
+    match segments {
+        0x00 => ' ',
+        0x3f => '0',
+        0x06 => '1',
+        0x5b => '2',
+        0x4f => '3',
+        0x66 => '4',
+        0x6d => '5',
+        0x7d => '6',
+        0x07 => '7',
+        0x7f => '8',
+        0x6f => '9',
+        _ => '?',
+    }
+}
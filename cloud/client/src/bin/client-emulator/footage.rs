@@ -0,0 +1,99 @@
+use std::{
+    fs::File,
+    io::{self, Read, Seek},
+    path::PathBuf,
+    sync::Arc,
+    time::Duration,
+};
+
+use anyhow::{bail, Context};
+use common_types::{IMAGE_HEIGHT, IMAGE_SIZE, IMAGE_WIDTH};
+use drivers::Simulator;
+use image::imageops::FilterType;
+use rgb565::Rgb565;
+
+// the rate the camera runs at on the board
+const FOOTAGE_FPS: u64 = 30;
+
+// what the camera sees, played on a loop
+pub enum Footage {
+    // one image per frame, in order of their names
+    Images { paths: Vec<PathBuf>, next: usize },
+    // frames of little-endian RGB565 back to back, like the server keeps them
+    Raw(File),
+}
+
+impl Footage {
+    pub fn open(path: PathBuf) -> anyhow::Result<Self> {
+        if !path.is_dir() {
+            return Ok(Self::Raw(File::open(path)?));
+        }
+
+        let mut paths = std::fs::read_dir(path)?
+            .map(|entry| Ok(entry?.path()))
+            .collect::<io::Result<Vec<_>>>()?;
+        paths.sort();
+        if paths.is_empty() {
+            bail!("No images to use as footage");
+        }
+
+        Ok(Self::Images { paths, next: 0 })
+    }
+
+    fn next_frame(&mut self) -> anyhow::Result<Vec<u16>> {
+        match self {
+            Self::Images { paths, next } => {
+                let path = &paths[*next];
+                *next = (*next + 1) % paths.len();
+
+                // images of any size are stretched to fit
+                let img = image::open(path)
+                    .with_context(|| format!("Failed to open {}", path.display()))?
+                    .resize_exact(
+                        IMAGE_WIDTH as u32,
+                        IMAGE_HEIGHT as u32,
+                        FilterType::Triangle,
+                    )
+                    .into_rgb8();
+
+                Ok(img
+                    .pixels()
+                    .map(|p| {
+                        let [r, g, b] = p.0;
+                        Rgb565::from_rgb888_components(r, g, b).to_rgb565()
+                    })
+                    .collect())
+            }
+            Self::Raw(file) => {
+                let mut data = vec![0u8; IMAGE_SIZE];
+                if let Err(e) = file.read_exact(&mut data) {
+                    if e.kind() != io::ErrorKind::UnexpectedEof {
+                        return Err(e.into());
+                    }
+                    // a partial frame at the end is dropped
+                    file.rewind()?;
+                    file.read_exact(&mut data)
+                        .context("Footage is shorter than a frame")?;
+                }
+
+                Ok(data
+                    .chunks_exact(2)
+                    .map(|c| u16::from_le_bytes([c[0], c[1]]))
+                    .collect())
+            }
+        }
+    }
+}
+
+// plays the footage into the camera's buffer while it's on
+pub async fn feed_loop(sim: Arc<Simulator>, mut footage: Footage) -> anyhow::Result<()> {
+    let mut interval = tokio::time::interval(Duration::from_millis(1000 / FOOTAGE_FPS));
+
+    loop {
+        interval.tick().await;
+
+        if sim.camera_enabled() {
+            sim.set_camera_frame(&footage.next_frame()?);
+        }
+    }
+}
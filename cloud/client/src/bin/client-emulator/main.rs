@@ -0,0 +1,136 @@
+mod display;
+mod footage;
+mod script;
+
+use std::{future, num::NonZeroUsize, path::PathBuf, sync::Arc, time::Duration};
+
+use clap::Parser;
+use drivers::{Calibration, DevMem, Simulator};
+use tokio::{select, time::Instant};
+
+use crate::footage::Footage;
+
+// runs the device client against simulated peripherals, e.g.
+// client-emulator --server-url ws://localhost:3000 --script squat.txt --footage frames/ --snapshots out/
+#[derive(Parser)]
+struct Args {
+    /// Servers to choose between with the slider switches, in order
+    #[arg(long, required = true)]
+    server_url: Vec<String>,
+    #[arg(long, default_value = client::DEVICE_ID)]
+    device_id: String,
+    #[arg(long, default_value_t = NonZeroUsize::new(30).unwrap())]
+    batch_size: NonZeroUsize,
+    /// Touchscreen calibration to use, otherwise touches map straight onto the screen like they
+    /// do on the simulated touchscreen
+    #[arg(long)]
+    calibration_file: Option<PathBuf>,
+    /// Keys and touches to play back, the emulator exits once they are done and whatever was
+    /// recorded has been sent
+    #[arg(long)]
+    script: Option<PathBuf>,
+    /// Directory of images, or a file of raw RGB565 frames, for the camera to see
+    #[arg(long)]
+    footage: Option<PathBuf>,
+    /// Directory to save the screen to whenever it changes
+    #[arg(long)]
+    snapshots: Option<PathBuf>,
+    /// File to keep the simulated memory in, so that it can be looked at while running
+    #[arg(long)]
+    memory: Option<PathBuf>,
+}
+
+#[tokio::main]
+async fn main() -> anyhow::Result<()> {
+    let Args {
+        server_url,
+        device_id,
+        batch_size,
+        calibration_file,
+        script,
+        footage,
+        snapshots,
+        memory,
+    } = Args::parse();
+
+    let sim = match memory {
+        Some(path) => Simulator::with_file(path)?,
+        None => Simulator::new()?,
+    };
+    let steps = script
+        .map(|path| script::parse(&std::fs::read_to_string(path)?))
+        .transpose()?;
+
+    if let Some(dir) = snapshots {
+        std::fs::create_dir_all(&dir)?;
+        spawn_logged(display::snapshot_loop(sim.clone(), dir));
+    }
+    if let Some(path) = footage {
+        spawn_logged(footage::feed_loop(sim.clone(), Footage::open(path)?));
+    }
+
+    let calibration_file = match calibration_file {
+        Some(path) => path,
+        None => {
+            let path = std::env::temp_dir().join("client-emulator-calibration.txt");
+            Calibration::default().save(&path)?;
+            path
+        }
+    };
+
+    select! {
+        res = client::run(
+            &server_url,
+            &device_id,
+            batch_size,
+            &calibration_file,
+            // the simulator has a keyboard and an audio port
+            true,
+            true,
+            DevMem::simulated(sim.clone()),
+        ) => res?,
+        _ = play(&sim, steps) => println!("Device is idle"),
+    }
+
+    Ok(())
+}
+
+async fn play(sim: &Arc<Simulator>, steps: Option<Vec<script::Step>>) {
+    match steps {
+        Some(steps) => {
+            script::play(sim, &steps).await;
+            println!("Script finished");
+            drain(sim).await;
+        }
+        // without a script, the device can only be driven from the app
+        None => future::pending().await,
+    }
+}
+
+// waits for the device to stop recording and send everything it has, so that a script can
+// end on the key that stops a video without cutting off the upload
+async fn drain(sim: &Simulator) {
+    const POLL_RATE: Duration = Duration::from_millis(10);
+    // the led goes off between one request and the next, so it has to stay off for a bit
+    const SETTLE_TIME: Duration = Duration::from_millis(250);
+
+    let mut idle_since = Instant::now();
+    loop {
+        let leds = sim.leds();
+        if leds[client::LED_RECORDING] || leds[client::LED_UPLOADING] {
+            idle_since = Instant::now();
+        } else if idle_since.elapsed() >= SETTLE_TIME {
+            return;
+        }
+
+        tokio::time::sleep(POLL_RATE).await;
+    }
+}
+
+fn spawn_logged(fut: impl future::Future<Output = anyhow::Result<()>> + Send + 'static) {
+    tokio::spawn(async move {
+        if let Err(e) = fut.await {
+            eprintln!("{e:?}");
+        }
+    });
+}
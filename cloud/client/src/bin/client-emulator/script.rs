@@ -0,0 +1,202 @@
+use std::time::Duration;
+
+use anyhow::{bail, Context};
+use common_types::{IMAGE_HEIGHT, IMAGE_WIDTH};
+use drivers::{
+    KeyboardKey, PenState, Simulator, SWITCH_COUNT, TOUCHSCREEN_HEIGHT, TOUCHSCREEN_WIDTH,
+};
+
+// long enough for the keys to be polled at least once
+const HOLD_TIME: Duration = Duration::from_millis(100);
+
+// one line of a script, with touches in vga coordinates:
+//   wait 2.5      pause for that many seconds
+//   key 0         press and release a key
+//   press 0       hold a key down
+//   release 0     let go of a key
+//   tap 160 120   touch the screen and let go
+//   down 160 120  put the pen down
+//   up 160 120    lift the pen up
+//   switch 3 on   flip a slider switch on or off
+//   type a        press and release a key on the keyboard, or one of
+//                 enter, escape, backspace, tab, space, up, down, left, right
+// anything after a # is a comment
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Step {
+    Wait(Duration),
+    Key(usize),
+    Press(usize),
+    Release(usize),
+    Tap(usize, usize),
+    Down(usize, usize),
+    Up(usize, usize),
+    Switch(usize, bool),
+    Type(KeyboardKey),
+}
+
+pub fn parse(script: &str) -> anyhow::Result<Vec<Step>> {
+    script
+        .lines()
+        .enumerate()
+        .filter_map(|(i, line)| {
+            let line = line.split('#').next().unwrap_or_default().trim();
+            (!line.is_empty()).then(|| parse_step(line).with_context(|| format!("Line {}", i + 1)))
+        })
+        .collect()
+}
+
+fn parse_step(line: &str) -> anyhow::Result<Step> {
+    let (command, args) = line.split_once(' ').unwrap_or((line, ""));
+    let args: Vec<_> = args.split_whitespace().collect();
+
+    let key = || -> anyhow::Result<usize> {
+        let [key] = args[..] else {
+            bail!("Expected a key");
+        };
+        let key = key.parse()?;
+        if key >= 4 {
+            bail!("There is no key {key}");
+        }
+        Ok(key)
+    };
+    let point = || -> anyhow::Result<(usize, usize)> {
+        let [x, y] = args[..] else {
+            bail!("Expected a point");
+        };
+        let (x, y) = (x.parse()?, y.parse()?);
+        if x >= IMAGE_WIDTH || y >= IMAGE_HEIGHT {
+            bail!("({x}, {y}) is off the screen");
+        }
+        Ok((x, y))
+    };
+
+    let step = match command {
+        "wait" => {
+            let [secs] = args[..] else {
+                bail!("Expected a number of seconds");
+            };
+            Step::Wait(Duration::try_from_secs_f64(secs.parse()?)?)
+        }
+        "key" => Step::Key(key()?),
+        "press" => Step::Press(key()?),
+        "release" => Step::Release(key()?),
+        "tap" => point().map(|(x, y)| Step::Tap(x, y))?,
+        "down" => point().map(|(x, y)| Step::Down(x, y))?,
+        "up" => point().map(|(x, y)| Step::Up(x, y))?,
+        "switch" => {
+            let [switch, state] = args[..] else {
+                bail!("Expected a switch and on or off");
+            };
+            let switch = switch.parse()?;
+            if switch >= SWITCH_COUNT {
+                bail!("There is no switch {switch}");
+            }
+            let on = match state {
+                "on" => true,
+                "off" => false,
+                _ => bail!("Expected on or off, not {state}"),
+            };
+            Step::Switch(switch, on)
+        }
+        "type" => {
+            let [name] = args[..] else {
+                bail!("Expected a key");
+            };
+            Step::Type(keyboard_key(name)?)
+        }
+        _ => bail!("Unknown command {command}"),
+    };
+
+    Ok(step)
+}
+
+fn keyboard_key(name: &str) -> anyhow::Result<KeyboardKey> {
+    let key = match name {
+        "enter" => KeyboardKey::Enter,
+        "escape" => KeyboardKey::Escape,
+        "backspace" => KeyboardKey::Backspace,
+        "tab" => KeyboardKey::Tab,
+        "space" => KeyboardKey::Char(' '),
+        "up" => KeyboardKey::Up,
+        "down" => KeyboardKey::Down,
+        "left" => KeyboardKey::Left,
+        "right" => KeyboardKey::Right,
+        _ => {
+            let mut chars = name.chars();
+            match (chars.next(), chars.next()) {
+                (Some(c @ ('a'..='z' | '0'..='9')), None) => KeyboardKey::Char(c),
+                _ => bail!("Unknown key {name}"),
+            }
+        }
+    };
+
+    Ok(key)
+}
+
+pub async fn play(sim: &Simulator, steps: &[Step]) {
+    // the simulated panel lines up exactly with the screen
+    let touch = |x, y, pen_state| {
+        sim.touch(
+            x * TOUCHSCREEN_WIDTH / IMAGE_WIDTH,
+            y * TOUCHSCREEN_HEIGHT / IMAGE_HEIGHT,
+            pen_state,
+        )
+    };
+
+    for &step in steps {
+        println!("Script: {step:?}");
+
+        match step {
+            Step::Wait(duration) => tokio::time::sleep(duration).await,
+            Step::Key(key) => {
+                sim.press_key(key);
+                tokio::time::sleep(HOLD_TIME).await;
+                sim.release_key(key);
+            }
+            Step::Press(key) => sim.press_key(key),
+            Step::Release(key) => sim.release_key(key),
+            Step::Tap(x, y) => {
+                touch(x, y, PenState::Down);
+                tokio::time::sleep(HOLD_TIME).await;
+                touch(x, y, PenState::Up);
+            }
+            Step::Down(x, y) => touch(x, y, PenState::Down),
+            Step::Up(x, y) => touch(x, y, PenState::Up),
+            Step::Switch(switch, on) => sim.set_switch(switch, on),
+            Step::Type(key) => {
+                sim.keyboard(key, true);
+                tokio::time::sleep(HOLD_TIME).await;
+                sim.keyboard(key, false);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_parse() {
+        let steps = parse(
+            "# pick a squat\nwait 1.5\ntap 50 50 # top left\n\nkey 0\nswitch 9 on\ntype enter\n",
+        )
+        .unwrap();
+        assert_eq!(
+            steps,
+            [
+                Step::Wait(Duration::from_millis(1500)),
+                Step::Tap(50, 50),
+                Step::Key(0),
+                Step::Switch(9, true),
+                Step::Type(KeyboardKey::Enter)
+            ]
+        );
+
+        assert!(parse("key 4").is_err());
+        assert!(parse("tap 320 0").is_err());
+        assert!(parse("jump").is_err());
+        assert!(parse("switch 10 on").is_err());
+        assert!(parse("type shift").is_err());
+    }
+}
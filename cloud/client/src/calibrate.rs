@@ -0,0 +1,86 @@
+use std::path::Path;
+
+use anyhow::Context;
+use common_types::{IMAGE_HEIGHT, IMAGE_WIDTH};
+use drivers::{
+    Align, Calibration, PenState, TextStyle, TouchEvent, TouchScreen, VgaDisplay,
+    CALIBRATION_TARGETS,
+};
+
+use crate::ui;
+
+const TARGET_SIZE: usize = 10;
+
+// uses the calibration from last time if there is one, otherwise asks for a new one and keeps it
+pub async fn load_or_calibrate(
+    path: &Path,
+    vga: &mut VgaDisplay,
+    touch: &TouchScreen,
+) -> anyhow::Result<Calibration> {
+    match Calibration::load(path) {
+        Ok(calibration) => Ok(calibration),
+        Err(e) => {
+            println!("No touchscreen calibration in {} ({e})", path.display());
+
+            let calibration = calibrate(vga, touch).await;
+            calibration
+                .save(path)
+                .context("Failed to save touchscreen calibration")?;
+            println!("Saved touchscreen calibration");
+
+            Ok(calibration)
+        }
+    }
+}
+
+// has the user touch each target in turn, until they're touched somewhere that makes sense
+pub async fn calibrate(vga: &mut VgaDisplay, touch: &TouchScreen) -> Calibration {
+    vga.erase_text();
+
+    loop {
+        let mut touches = [(0, 0); 3];
+        for (touched, &target) in touches.iter_mut().zip(&CALIBRATION_TARGETS) {
+            draw_target(vga, target).await;
+            *touched = wait_touch(touch).await;
+        }
+
+        if let Some(calibration) = Calibration::from_touches(touches) {
+            return calibration;
+        }
+        println!("Calibration touches were in a line, trying again");
+    }
+}
+
+async fn draw_target(vga: &mut VgaDisplay, (x, y): (usize, usize)) {
+    vga.draw_box(0, 0, IMAGE_WIDTH - 1, IMAGE_HEIGHT - 1, ui::BACKGROUND);
+    vga.draw_text(
+        0,
+        104,
+        IMAGE_WIDTH,
+        "Touch the middle of the cross",
+        &TextStyle {
+            color: ui::ACCENT,
+            align: Align::Center,
+            ..Default::default()
+        },
+    );
+
+    vga.draw_line(x - TARGET_SIZE, y, x + TARGET_SIZE, y, ui::ACCENT);
+    vga.draw_line(x, y - TARGET_SIZE, x, y + TARGET_SIZE, ui::ACCENT);
+    vga.sync_screen().await;
+}
+
+// where the pen was lifted, in raw coordinates, since it has settled by then
+async fn wait_touch(touch: &TouchScreen) -> (usize, usize) {
+    touch.flush().await;
+
+    let mut down = false;
+    loop {
+        let TouchEvent { x, y, pen_state } = touch.read().await;
+        match pen_state {
+            PenState::Down => down = true,
+            PenState::Up if down => return (x, y),
+            PenState::Up => {}
+        }
+    }
+}
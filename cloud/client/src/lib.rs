@@ -0,0 +1,780 @@
+mod calibrate;
+mod qr;
+pub mod ui;
+
+use std::{
+    convert::Infallible,
+    num::NonZeroUsize,
+    path::Path,
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+use anyhow::{bail, Context};
+use common_types::{
+    Cue, DeviceRequest, DeviceResponse, SessionId, UserId, VideoRequest, WorkoutType, IMAGE_HEIGHT,
+    IMAGE_WIDTH, MAX_VIDEO_LENGTH,
+};
+use futures::{
+    stream::{SplitSink, SplitStream},
+    Future, SinkExt, StreamExt,
+};
+use rgb565::Rgb565;
+use tokio::{
+    net::TcpStream,
+    pin, select,
+    sync::{
+        mpsc::{self, UnboundedReceiver, UnboundedSender},
+        oneshot,
+    },
+    time::{Instant, MissedTickBehavior},
+};
+use tokio_tungstenite::{tungstenite::Message, MaybeTlsStream, WebSocketStream};
+use uuid::Uuid;
+
+use crate::{calibrate::load_or_calibrate, qr::qr_texture, ui::Screen};
+use drivers::{
+    tone, Align, AudioOut, Camera, DevMem, Gesture, HexDisplay, Input, InputEvent, IntervalTimer,
+    KeyboardEvent, KeyboardKey, Keys, Leds, Ps2Keyboard, Switches, TextStyle, Texture, TouchScreen,
+    VgaDisplay,
+};
+
+const CAMERA_FPS: u32 = 30;
+
+// what each of the red leds shows
+pub const LED_SERVER: usize = 0; // connected to the server
+pub const LED_USER: usize = 1; // a phone is connected
+pub const LED_RECORDING: usize = 2;
+pub const LED_UPLOADING: usize = 3; // video is being sent to the server
+
+// beeped for each second of the countdown, then once more when recording starts
+const TICK_TONE: (f64, Duration) = (880.0, Duration::from_millis(60));
+const START_TONE: (f64, Duration) = (1320.0, Duration::from_millis(400));
+pub const DEVICE_ID: &str = "38469b2b-58db-40db-9bb3-e833eb043b30";
+
+// space above the qr code for telling the user what to do with it
+const QR_CAPTION: &str = "Scan to connect";
+const QR_CAPTION_HEIGHT: usize = 24;
+
+// change as needed
+const COUNTDOWN_TEXTURE_PATH: &str = "Countdown.png";
+
+const CONNECTION_TIMEOUT: Duration = Duration::from_secs(20);
+
+type WsReadHalf = SplitStream<WebSocketStream<MaybeTlsStream<TcpStream>>>;
+type WsWriteHalf = SplitSink<WebSocketStream<MaybeTlsStream<TcpStream>>, Message>;
+
+struct Peripherals {
+    input: Input,
+    camera: Camera,
+    vga: VgaDisplay,
+    hex: HexDisplay,
+    timer: IntervalTimer,
+    // also lit by the task sending to the server
+    leds: Arc<Mutex<Leds>>,
+    // also played from when the server sends a cue
+    audio: AudioOut,
+}
+
+struct Resources {
+    qr_texture: Texture,
+    countdown_texture: Texture,
+    batch_size: usize,
+}
+
+// runs the device against the server until the connection is closed
+// the slider switches pick which of the servers to use
+pub async fn run(
+    server_urls: &[String],
+    device_id: &str,
+    batch_size: NonZeroUsize,
+    calibration_file: &Path,
+    ps2_keyboard: bool,
+    audio: bool,
+    mem: DevMem,
+) -> anyhow::Result<()> {
+    let mut vga = VgaDisplay::new(&mem)?;
+    let mut touch = TouchScreen::new(&mem)?;
+
+    // before connecting, since the server won't wait around for it
+    let calibration = load_or_calibrate(calibration_file, &mut vga, &touch).await?;
+    touch.set_calibration(calibration);
+
+    let perif = Peripherals {
+        input: Input::new(
+            Keys::new(&mem)?,
+            touch,
+            Switches::new(&mem)?,
+            ps2_keyboard.then(|| Ps2Keyboard::new(&mem)).transpose()?,
+        ),
+        camera: Camera::new(&mem)?,
+        vga,
+        hex: HexDisplay::new(&mem)?,
+        timer: IntervalTimer::new(&mem)?,
+        leds: Arc::new(Mutex::new(Leds::new(&mem)?)),
+        audio: if audio {
+            AudioOut::new(&mem)?
+        } else {
+            AudioOut::silent()
+        },
+    };
+    println!("Opened peripherals");
+
+    // as a binary number, with SW0 as the lowest bit
+    let server = perif
+        .input
+        .switches()
+        .iter()
+        .rev()
+        .fold(0, |n, &on| n << 1 | on as usize);
+    let server_url = server_urls
+        .get(server)
+        .with_context(|| format!("No server for switches set to {server}"))?;
+
+    let ws_url = format!("{server_url}/device?id={device_id}");
+    println!("Trying to connect to {ws_url}");
+
+    // TODO: do we need any handling in case the server does not stay up? e.g., retry loop?
+    let (ws, _) = tokio_tungstenite::connect_async(ws_url)
+        .await
+        .context("Failed to create websocket")?;
+    println!("Websocket connected");
+    perif.leds.lock().unwrap().set(LED_SERVER, true);
+
+    // we should have: qr code, workout selection, start workout
+    let resources = Resources {
+        // the app connects to whatever device id it scans
+        qr_texture: qr_texture(device_id, IMAGE_WIDTH, IMAGE_HEIGHT - QR_CAPTION_HEIGHT)?,
+        countdown_texture: load_texture(COUNTDOWN_TEXTURE_PATH).await?,
+        batch_size: batch_size.into(),
+    };
+    println!("Loaded resources");
+
+    let (req_tx, req_rx) = mpsc::unbounded_channel();
+    let (res_tx, res_rx) = mpsc::unbounded_channel();
+    let (shutdown_tx, shutdown_rx) = oneshot::channel();
+    let (ws_tx, ws_rx) = ws.split();
+
+    spawn_logged(ws_send_loop(req_rx, ws_tx, perif.leds.clone()));
+    spawn_logged(ws_recv_loop(ws_rx, res_tx, shutdown_tx));
+
+    let leds = perif.leds.clone();
+    select! {
+        res = connection_loop(res_rx, req_tx, perif, resources) => {
+            res?;
+        }
+        _ = shutdown_rx => {}
+    }
+
+    println!("Exiting client");
+    leds.lock().unwrap().clear();
+
+    // Trying to close the websocket will time out if the connection is already closed
+    // so we can just drop the connection
+
+    Ok(())
+}
+
+// images in any format are converted, keeping their transparency if they have any
+async fn load_texture(path: &str) -> anyhow::Result<Texture> {
+    let img = image::open(path)
+        .with_context(|| format!("Failed to load {path}"))?
+        .into_rgba8();
+    let (data, alpha) = img
+        .pixels()
+        .map(|p| {
+            let [r, g, b, a] = p.0;
+            (Rgb565::from_rgb888_components(r, g, b).to_rgb565(), a)
+        })
+        .unzip();
+
+    Ok(Texture::with_alpha(
+        img.width() as usize,
+        img.height() as usize,
+        data,
+        alpha,
+    ))
+}
+
+fn spawn_logged<T>(fut: impl Future<Output = anyhow::Result<T>> + Send + 'static) {
+    tokio::spawn(async move {
+        if let Err(e) = fut.await {
+            eprintln!("{e:?}");
+        }
+    });
+}
+
+async fn connection_loop(
+    mut res_rx: UnboundedReceiver<DeviceResponse>,
+    mut req_tx: UnboundedSender<DeviceRequest>,
+    mut perif: Peripherals,
+    resources: Resources,
+) -> anyhow::Result<()> {
+    loop {
+        handle_connection(&mut res_rx, &mut req_tx, &mut perif, &resources).await?;
+    }
+}
+
+// just for forwarding all received messages
+// other end can do whatever they want
+async fn ws_recv_loop(
+    mut ws_rx: WsReadHalf,
+    res_tx: UnboundedSender<DeviceResponse>,
+    _shutdown_tx: oneshot::Sender<Infallible>, // gets dropped if connection to server is cut
+) -> anyhow::Result<()> {
+    println!("Spawned ws_recv_loop");
+
+    loop {
+        let msg = tokio::time::timeout(CONNECTION_TIMEOUT, ws_rx.next())
+            .await
+            .context("Server connection timed out")?
+            .context("No response from server")??;
+
+        let res: DeviceResponse = match msg {
+            Message::Text(msg) => serde_json::from_str(&msg)?,
+            Message::Pong(_) => {
+                println!("Received pong");
+                continue;
+            }
+            Message::Close(_) => {
+                bail!("Server closed connection")
+            }
+            _ => {
+                bail!("Unexpected message from server: {msg:?}")
+            }
+        };
+
+        res_tx.send(res)?;
+    }
+}
+
+async fn ws_send_loop(
+    mut req_rx: UnboundedReceiver<DeviceRequest>,
+    mut ws_tx: WsWriteHalf,
+    leds: Arc<Mutex<Leds>>,
+) -> anyhow::Result<()> {
+    println!("Spawned ws_send_loop");
+
+    const PING_INTERVAL: Duration = Duration::from_secs(5);
+    let mut ping = tokio::time::interval(PING_INTERVAL);
+
+    // in case sending video takes too long, we don't care about "catching up"
+    ping.set_missed_tick_behavior(MissedTickBehavior::Delay);
+
+    loop {
+        select! {
+            _ = ping.tick() => {
+                println!("Sending ping");
+                ws_tx.send(Message::Ping(vec![])).await?;
+            }
+            req = req_rx.recv() => {
+                let Some(req) = req else {
+                    break;
+                };
+
+                let start = Instant::now();
+                let msg = Message::Binary(bincode::serialize(&req)?);
+
+                leds.lock().unwrap().set(LED_UPLOADING, true);
+                ws_tx.send(msg).await?;
+                leds.lock().unwrap().set(LED_UPLOADING, false);
+
+                let end = Instant::now();
+                println!("Sending data took: {} ms", (end - start).as_millis());
+            }
+            _ = tokio::signal::ctrl_c() => {
+                // try to close
+                ws_tx.close().await?;
+                break;
+            }
+        }
+    }
+
+    Ok(())
+}
+
+async fn handle_connection(
+    res_rx: &mut UnboundedReceiver<DeviceResponse>,
+    req_tx: &mut UnboundedSender<DeviceRequest>,
+    perif: &mut Peripherals,
+    resources: &Resources,
+) -> anyhow::Result<()> {
+    show_qr_code(&mut perif.vga, resources).await;
+    let user_id = wait_connection(res_rx).await?;
+    let leds = perif.leds.clone();
+    leds.lock().unwrap().set(LED_USER, true);
+    let audio = perif.audio.clone();
+
+    // commands from the user's phone and errors from the server get passed along to the workout
+    let (cmd_tx, mut cmd_rx) = mpsc::unbounded_channel();
+
+    // the video currently being recorded, if any
+    let mut session = None;
+
+    // main part of workout
+    select! {
+        res = wait_disconnection(res_rx, cmd_tx, &audio) => {
+            // TODO: add another screen here?
+
+            // NOTE: in really bad circumstances, there could potentially be more than one video in the outgoing queue
+            // if a video gets cut off part way, we don't want to flush the entire queue!
+            // so we will simply not using flushing altogether
+
+            // instead, just signal that the last video, if it wasn't done, should be cancelled
+            if let Some(session_id) = session.take() {
+                req_tx.send(DeviceRequest {
+                    session_id,
+                    req: VideoRequest::Cancel,
+                })?;
+            }
+
+            res?;
+        }
+        res = do_workout(req_tx, perif, user_id, resources, &mut cmd_rx, &mut session) => {
+            res?;
+        }
+    }
+
+    // the recording may have been cut off
+    let mut leds = leds.lock().unwrap();
+    leds.set(LED_USER, false);
+    leds.set(LED_RECORDING, false);
+
+    Ok(())
+}
+
+async fn show_qr_code(vga: &mut VgaDisplay, resources: &Resources) {
+    vga.erase_text();
+    vga.draw_box(0, 0, IMAGE_WIDTH - 1, IMAGE_HEIGHT - 1, 0xFFFF);
+    vga.draw_text(
+        0,
+        4,
+        IMAGE_WIDTH,
+        QR_CAPTION,
+        &TextStyle {
+            scale: 2,
+            color: 0x0000,
+            align: Align::Center,
+            ..Default::default()
+        },
+    );
+
+    let qr = &resources.qr_texture;
+    vga.draw_texture(
+        (IMAGE_WIDTH - qr.width()) / 2,
+        QR_CAPTION_HEIGHT + (IMAGE_HEIGHT - QR_CAPTION_HEIGHT - qr.height()) / 2,
+        qr,
+    );
+    vga.sync_screen().await;
+}
+
+async fn wait_connection(ws_rx: &mut UnboundedReceiver<DeviceResponse>) -> anyhow::Result<UserId> {
+    loop {
+        if let DeviceResponse::Connected { user_id } = ws_rx.recv().await.context("ws_rx closed")? {
+            println!("Connected to user id: {user_id}");
+            return Ok(user_id);
+        }
+    }
+}
+
+async fn wait_disconnection(
+    ws_rx: &mut UnboundedReceiver<DeviceResponse>,
+    cmd_tx: UnboundedSender<DeviceResponse>,
+    audio: &AudioOut,
+) -> anyhow::Result<()> {
+    loop {
+        match ws_rx.recv().await.context("ws_rx closed")? {
+            DeviceResponse::Disconnected => {
+                println!("Disconnected from user");
+                return Ok(());
+            }
+            cmd @ (DeviceResponse::SelectWorkout { .. }
+            | DeviceResponse::StartRecording
+            | DeviceResponse::StopRecording) => {
+                println!("Received command: {cmd:?}");
+                cmd_tx.send(cmd)?;
+            }
+            err @ DeviceResponse::Error { .. } => {
+                println!("Received error: {err:?}");
+                cmd_tx.send(err)?;
+            }
+            // only matters for the video being recorded, which the workout knows about
+            cue @ DeviceResponse::Cue {
+                cue: Cue::NearLimit,
+                ..
+            } => {
+                println!("Received cue: {cue:?}");
+                cmd_tx.send(cue)?;
+            }
+            DeviceResponse::Cue { cue, .. } => {
+                println!("Received cue: {cue:?}");
+                audio.play(cue_sound(cue));
+            }
+            res => println!("Unexpected response: {res:?}"),
+        }
+    }
+}
+
+// what each of the server's cues sounds like, as (frequency, duration in ms) notes
+fn cue_sound(cue: Cue) -> Vec<i32> {
+    let notes: &[(f64, u64)] = match cue {
+        // three quick beeps
+        Cue::NearLimit => &[
+            (660.0, 100),
+            (0.0, 100),
+            (660.0, 100),
+            (0.0, 100),
+            (660.0, 100),
+        ],
+        // rising, like a notification
+        Cue::Analyzed => &[(660.0, 150), (990.0, 250)],
+    };
+
+    notes
+        .iter()
+        .flat_map(|&(frequency, ms)| tone(frequency, Duration::from_millis(ms)))
+        .collect()
+}
+
+// errors go on the char buffer, so they show up over whatever is on screen
+fn show_error(vga: &mut VgaDisplay, message: &str) {
+    vga.erase_text();
+    vga.write_text(1, 1, &format!("Error: {message}"));
+}
+
+// waits until `f` accepts one of the relayed commands
+// commands that don't apply to the current screen are dropped
+async fn wait_command<T>(
+    cmd_rx: &mut UnboundedReceiver<DeviceResponse>,
+    mut f: impl FnMut(DeviceResponse) -> Option<T>,
+) -> anyhow::Result<T> {
+    loop {
+        let cmd = cmd_rx.recv().await.context("cmd_rx closed")?;
+        if let Some(res) = f(cmd) {
+            return Ok(res);
+        }
+    }
+}
+
+// waits until `f` accepts one of the user's inputs
+async fn wait_input<T>(input: &mut Input, mut f: impl FnMut(InputEvent) -> Option<T>) -> T {
+    loop {
+        if let Some(res) = f(input.next().await) {
+            return res;
+        }
+    }
+}
+
+// be careful not to block for too long in here
+async fn do_workout(
+    req_tx: &mut UnboundedSender<DeviceRequest>,
+    perif: &mut Peripherals,
+    user_id: UserId,
+    resources: &Resources,
+    cmd_rx: &mut UnboundedReceiver<DeviceResponse>,
+    session: &mut Option<SessionId>,
+) -> anyhow::Result<()> {
+    loop {
+        let Peripherals { input, vga, .. } = perif;
+
+        let workout_type = select_workout(input, vga, cmd_rx).await?;
+        println!("Selected workout: {workout_type:?}");
+        start_workout(input, vga, workout_type, cmd_rx).await?;
+        println!("Starting workout");
+        record_workout(
+            req_tx,
+            perif,
+            workout_type,
+            &user_id,
+            resources,
+            cmd_rx,
+            session,
+        )
+        .await?;
+        println!("Stopped workout");
+    }
+}
+
+async fn select_workout(
+    input: &mut Input,
+    vga: &mut VgaDisplay,
+    cmd_rx: &mut UnboundedReceiver<DeviceResponse>,
+) -> anyhow::Result<WorkoutType> {
+    let mut screen = Screen::new();
+    screen.label(
+        "Pick a workout",
+        TextStyle {
+            scale: 2,
+            color: ui::ACCENT,
+            align: Align::Center,
+            ..Default::default()
+        },
+    );
+    let list = screen.list(WorkoutType::ALL.map(|w| w.name().to_string()).to_vec());
+
+    vga.erase_text();
+    input.flush();
+
+    let workout_type = loop {
+        if screen.is_dirty() {
+            screen.draw(vga).await;
+        }
+
+        select! {
+            event = input.next() => match event {
+                // picked with the key of the same index too
+                InputEvent::KeyDown(i) if i < WorkoutType::ALL.len() => break WorkoutType::ALL[i],
+                InputEvent::Touch(gesture) => {
+                    if let Some(ui::Event::Selected(id, i)) = screen.handle(gesture) {
+                        if id == list {
+                            break WorkoutType::ALL[i];
+                        }
+                    }
+                }
+                _ => {}
+            },
+            workout_type = wait_command(cmd_rx, |cmd| match cmd {
+                DeviceResponse::SelectWorkout { workout_type } => Some(workout_type),
+                // nothing is being recorded, so any about a video are old news
+                DeviceResponse::Error { session_id: None, message, .. } => {
+                    show_error(vga, &message);
+                    None
+                }
+                _ => None,
+            }) => break workout_type?,
+        }
+    };
+
+    Ok(workout_type)
+}
+
+async fn start_workout(
+    input: &mut Input,
+    vga: &mut VgaDisplay,
+    workout_type: WorkoutType,
+    cmd_rx: &mut UnboundedReceiver<DeviceResponse>,
+) -> anyhow::Result<()> {
+    let mut screen = Screen::new();
+    screen.label(
+        workout_type.name(),
+        TextStyle {
+            scale: 2,
+            color: ui::ACCENT,
+            align: Align::Center,
+            ..Default::default()
+        },
+    );
+    let start = screen.button("Start");
+
+    vga.erase_text();
+    screen.draw(vga).await;
+    input.flush();
+
+    // TODO: remove keys later?
+
+    select! {
+        _ = wait_input(input, |event| match event {
+            InputEvent::KeyDown(0) | InputEvent::Keyboard(KeyboardEvent {
+                key: KeyboardKey::Enter,
+                pressed: true,
+            }) => Some(()),
+            InputEvent::Touch(gesture) => {
+                (screen.handle(gesture) == Some(ui::Event::Pressed(start))).then_some(())
+            }
+            _ => None,
+        }) => {}
+        res = wait_command(cmd_rx, |cmd| match cmd {
+            DeviceResponse::StartRecording => Some(()),
+            DeviceResponse::Error { session_id: None, message, .. } => {
+                show_error(vga, &message);
+                None
+            }
+            _ => None,
+        }) => res?,
+    }
+
+    Ok(())
+}
+
+async fn record_workout(
+    req_tx: &mut UnboundedSender<DeviceRequest>,
+    perif: &mut Peripherals,
+    workout_type: WorkoutType,
+    user_id: &UserId,
+    resources: &Resources,
+    cmd_rx: &mut UnboundedReceiver<DeviceResponse>,
+    session: &mut Option<SessionId>,
+) -> anyhow::Result<()> {
+    // the camera draws over the screen's pixels, so it's only used to lay out the button
+    let mut screen = Screen::new();
+    let stop = screen.button("Stop");
+
+    let Peripherals {
+        input,
+        camera,
+        vga,
+        hex,
+        timer,
+        leds,
+        audio,
+    } = perif;
+
+    vga.draw_texture(0, 0, &resources.countdown_texture);
+    vga.sync_screen().await;
+
+    let mut countdown = tokio::time::interval(Duration::from_secs(1));
+
+    // countdown for ten seconds
+    countdown.tick().await;
+    hex.write([
+        0,
+        0,
+        0,
+        0,
+        HexDisplay::digit_to_hex(1),
+        HexDisplay::digit_to_hex(0),
+    ]);
+    audio.tone(TICK_TONE.0, TICK_TONE.1);
+
+    for i in (5..=9).rev() {
+        countdown.tick().await;
+
+        hex.write([0, 0, 0, 0, 0, HexDisplay::digit_to_hex(i)]);
+        audio.tone(TICK_TONE.0, TICK_TONE.1);
+    }
+
+    // switch to camera, which writes to the front buffer
+    if !vga.is_front() {
+        vga.sync_screen().await;
+    }
+
+    // enable camera
+    let guard = camera.enable();
+    screen.write_labels(vga);
+
+    for i in (1..=4).rev() {
+        countdown.tick().await;
+
+        hex.write([0, 0, 0, 0, 0, HexDisplay::digit_to_hex(i)]);
+        audio.tone(TICK_TONE.0, TICK_TONE.1);
+    }
+
+    // one more to show the 1
+    countdown.tick().await;
+
+    // add a small sleep to reduce chance of tearing the first frame
+    tokio::time::sleep(Duration::from_millis(20)).await;
+
+    timer.start(Duration::from_secs(1) / CAMERA_FPS);
+    leds.lock().unwrap().set(LED_RECORDING, true);
+    audio.tone(START_TONE.0, START_TONE.1);
+    let timeout = tokio::time::sleep(MAX_VIDEO_LENGTH);
+    let mut frames = vec![];
+
+    let mut hex_timer = tokio::time::interval(Duration::from_secs(1));
+    let mut min = 0;
+    let mut sec = 0;
+
+    pin!(timeout);
+
+    // every video request also needs to contain the session id
+    let session_id = SessionId::from(Uuid::new_v4().to_string());
+    *session = Some(session_id.clone());
+
+    let send = |req| {
+        req_tx.send(DeviceRequest {
+            session_id: session_id.clone(),
+            req,
+        })
+    };
+
+    send(VideoRequest::Start {
+        user_id: user_id.clone(),
+        workout_type,
+    })?;
+
+    input.flush();
+
+    // wait until key 0, next frame, or maximum video length
+    select! {
+        // one for loop
+        res = async {
+            loop {
+                timer.tick().await;
+
+                let frame = guard.capture_frame();
+                frames.push(frame);
+
+                if frames.len() == resources.batch_size {
+                    send(VideoRequest::Frames(std::mem::take(&mut frames)))?;
+                }
+            }
+
+            #[allow(unreachable_code)]
+            Ok::<(), anyhow::Error>(())
+        } => { res?; }
+        _ = async {
+            loop {
+                hex_timer.tick().await;
+
+                display_time(hex, min, sec);
+
+                // update time
+                sec += 1;
+                if sec == 60 {
+                    sec = 0;
+                    min += 1;
+                }
+            }
+        } => {}
+        // others don't loop
+        _ = &mut timeout => {}
+        _ = wait_input(input, |event| match event {
+            InputEvent::KeyDown(0) | InputEvent::Keyboard(KeyboardEvent {
+                key: KeyboardKey::Escape,
+                pressed: true,
+            }) => Some(()),
+            InputEvent::Touch(Gesture::Tap(point)) if screen.button_at(point) == Some(stop) => {
+                Some(())
+            }
+            _ => None,
+        }) => {}
+        // the server may have dropped the video, in which case there's no point carrying on
+        res = wait_command(cmd_rx, |cmd| match cmd {
+            DeviceResponse::StopRecording => Some(()),
+            DeviceResponse::Cue { session_id: id, cue } if id == session_id => {
+                audio.play(cue_sound(cue));
+                None
+            }
+            // one about an earlier video shouldn't stop this one
+            DeviceResponse::Error { session_id: Some(id), .. } if id != session_id => None,
+            DeviceResponse::Error { message, recoverable, .. } => {
+                show_error(vga, &message);
+                screen.write_labels(vga);
+                (!recoverable).then_some(())
+            }
+            _ => None,
+        }) => res?,
+    }
+
+    // clear timer
+    hex.clear();
+    timer.stop();
+    leds.lock().unwrap().set(LED_RECORDING, false);
+
+    if !frames.is_empty() {
+        send(VideoRequest::Frames(frames))?;
+    }
+
+    send(VideoRequest::Done)?;
+    *session = None;
+
+    Ok(())
+}
+
+fn display_time(hex: &mut HexDisplay, min: u8, sec: u8) {
+    let m0 = HexDisplay::digit_to_hex(min);
+    let s1 = HexDisplay::digit_to_hex(sec / 10);
+    let s0 = HexDisplay::digit_to_hex(sec % 10);
+    hex.write([0, 0, 0, m0, s1, s0]);
+}
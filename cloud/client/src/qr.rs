@@ -0,0 +1,41 @@
+use drivers::Texture;
+use qrcode::{Color, QrCode};
+
+// blank modules needed around the code for it to scan reliably
+const QUIET_ZONE: usize = 4;
+
+const DARK: u16 = 0x0000;
+const LIGHT: u16 = 0xFFFF;
+
+// renders data as the largest square qr code that fits, quiet zone included
+pub(super) fn qr_texture(
+    data: &str,
+    max_width: usize,
+    max_height: usize,
+) -> anyhow::Result<Texture> {
+    let code = QrCode::new(data)?;
+    let modules = code.width();
+    let colors = code.to_colors();
+
+    let size = modules + 2 * QUIET_ZONE;
+    let scale = max_width.min(max_height) / size;
+    anyhow::ensure!(scale > 0, "QR code with {modules} modules does not fit");
+
+    let width = size * scale;
+    let pixels = (0..width * width)
+        .map(|i| {
+            let (x, y) = (i % width / scale, i / width / scale);
+
+            // the quiet zone wraps around the code
+            let (Some(x), Some(y)) = (x.checked_sub(QUIET_ZONE), y.checked_sub(QUIET_ZONE)) else {
+                return LIGHT;
+            };
+            match (x < modules && y < modules).then(|| colors[y * modules + x]) {
+                Some(Color::Dark) => DARK,
+                _ => LIGHT,
+            }
+        })
+        .collect();
+
+    Ok(Texture::new(width, width, pixels))
+}
@@ -0,0 +1,530 @@
+// a small widget layer for the device's screens
+// widgets are stacked down the screen in the order they are added, and the
+// whole screen is redrawn whenever any of them changes
+
+use common_types::{IMAGE_HEIGHT, IMAGE_WIDTH};
+use drivers::{text_height, Align, Direction, Gesture, Rect, TextStyle, VgaDisplay, GLYPH_SIZE};
+
+// colours to match the pictures
+pub const BACKGROUND: u16 = 0x867D;
+pub const ACCENT: u16 = 0x19CD;
+pub const FOREGROUND: u16 = 0xFFFF;
+
+const PADDING: usize = 8;
+const SPACING: usize = 6;
+const TEXT_SCALE: usize = 2;
+const BUTTON_HEIGHT: usize = 32;
+const ROW_HEIGHT: usize = 32;
+const PROGRESS_HEIGHT: usize = 12;
+const DIALOG_WIDTH: usize = 256;
+// the char buffer has a character for every 4x4 pixels
+const CHAR_SIZE: usize = 4;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct WidgetId(usize);
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Event {
+    Pressed(WidgetId),
+    // an item of a list, counting from the first item rather than the first shown
+    Selected(WidgetId, usize),
+    // one of the dialog's buttons, which also closes it
+    Dismissed(usize),
+}
+
+enum Widget {
+    Label { text: String, style: TextStyle },
+    Button { text: String },
+    // `first` is the first item on the page being shown
+    List { items: Vec<String>, first: usize },
+    Progress { value: f64 },
+}
+
+struct Node {
+    rect: Rect,
+    widget: Widget,
+}
+
+struct Dialog {
+    message: String,
+    buttons: Vec<String>,
+}
+
+// what a point on the screen lands on
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Target {
+    Node(usize),
+    Item(usize, usize),
+    PageBack(usize),
+    PageForward(usize),
+    DialogButton(usize),
+}
+
+pub struct Screen {
+    nodes: Vec<Node>,
+    // where the next widget goes
+    next_y: usize,
+    // while a dialog is open, nothing else can be touched
+    dialog: Option<Dialog>,
+    dirty: bool,
+}
+
+impl Default for Screen {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Screen {
+    pub fn new() -> Self {
+        Self {
+            nodes: vec![],
+            next_y: PADDING,
+            dialog: None,
+            dirty: true,
+        }
+    }
+
+    // takes up the next `height` pixels down the screen
+    fn place(&mut self, height: usize, widget: Widget) -> WidgetId {
+        assert!(
+            self.next_y + height <= IMAGE_HEIGHT - PADDING,
+            "widgets don't fit on the screen"
+        );
+
+        let rect = Rect {
+            x: PADDING,
+            y: self.next_y,
+            width: IMAGE_WIDTH - 2 * PADDING,
+            height,
+        };
+        self.next_y += height + SPACING;
+        self.nodes.push(Node { rect, widget });
+        self.dirty = true;
+
+        WidgetId(self.nodes.len() - 1)
+    }
+
+    // labels keep the space they were given, even if their text changes
+    pub fn label(&mut self, text: &str, style: TextStyle) -> WidgetId {
+        let height = text_height(text, IMAGE_WIDTH - 2 * PADDING, style.scale);
+        self.place(
+            height,
+            Widget::Label {
+                text: text.into(),
+                style,
+            },
+        )
+    }
+
+    pub fn button(&mut self, text: &str) -> WidgetId {
+        self.place(BUTTON_HEIGHT, Widget::Button { text: text.into() })
+    }
+
+    // fills the rest of the screen, with pages if the items don't fit
+    pub fn list(&mut self, items: Vec<String>) -> WidgetId {
+        let height = (IMAGE_HEIGHT - PADDING).saturating_sub(self.next_y);
+        assert!(height / ROW_HEIGHT >= 2, "no room for a list");
+
+        self.place(height, Widget::List { items, first: 0 })
+    }
+
+    pub fn progress(&mut self, value: f64) -> WidgetId {
+        self.place(PROGRESS_HEIGHT, Widget::Progress { value })
+    }
+
+    pub fn set_text(&mut self, WidgetId(id): WidgetId, new_text: &str) {
+        match &mut self.nodes[id].widget {
+            Widget::Label { text, .. } | Widget::Button { text } => *text = new_text.into(),
+            _ => panic!("Widget has no text"),
+        }
+        self.dirty = true;
+    }
+
+    pub fn set_progress(&mut self, WidgetId(id): WidgetId, new_value: f64) {
+        let Widget::Progress { value } = &mut self.nodes[id].widget else {
+            panic!("Widget is not a progress bar");
+        };
+        *value = new_value;
+        self.dirty = true;
+    }
+
+    pub fn show_dialog(&mut self, message: &str, buttons: &[&str]) {
+        assert!(!buttons.is_empty(), "dialog could never be closed");
+
+        self.dialog = Some(Dialog {
+            message: message.into(),
+            buttons: buttons.iter().map(|&button| button.into()).collect(),
+        });
+        self.dirty = true;
+    }
+
+    // whether something changed since the screen was last drawn
+    pub fn is_dirty(&self) -> bool {
+        self.dirty
+    }
+
+    pub async fn draw(&mut self, vga: &mut VgaDisplay) {
+        fill(vga, full_screen(), BACKGROUND);
+
+        for node in &self.nodes {
+            let rect = node.rect;
+
+            match &node.widget {
+                Widget::Label { text, style } => {
+                    vga.draw_text(rect.x, rect.y, rect.width, text, style);
+                }
+                Widget::Button { text } => draw_button(vga, rect, text),
+                Widget::List { items, first } => {
+                    let (rows, paged) = list_rows(rect, items.len());
+
+                    for (row, item) in items.iter().skip(*first).take(rows).enumerate() {
+                        draw_button(vga, row_rect(rect, row), item);
+                    }
+                    if paged {
+                        let (back, forward) = page_rects(rect, rows);
+                        draw_button(vga, back, "<");
+                        draw_button(vga, forward, ">");
+                    }
+                }
+                Widget::Progress { value } => {
+                    fill(vga, rect, ACCENT);
+                    let inner = inset(rect, 2);
+                    fill(vga, inner, FOREGROUND);
+
+                    let done = (inner.width as f64 * value.clamp(0.0, 1.0)).round() as usize;
+                    if done > 0 {
+                        fill(
+                            vga,
+                            Rect {
+                                width: done,
+                                ..inner
+                            },
+                            ACCENT,
+                        );
+                    }
+                }
+            }
+        }
+
+        if let Some(dialog) = &self.dialog {
+            let (rect, buttons) = dialog_rects(dialog);
+            fill(vga, rect, ACCENT);
+            fill(vga, inset(rect, 2), FOREGROUND);
+
+            vga.draw_text(
+                rect.x + PADDING,
+                rect.y + PADDING,
+                rect.width - 2 * PADDING,
+                &dialog.message,
+                &TextStyle {
+                    scale: TEXT_SCALE,
+                    color: ACCENT,
+                    align: Align::Center,
+                    ..Default::default()
+                },
+            );
+            for (button, text) in buttons.into_iter().zip(&dialog.buttons) {
+                draw_button(vga, button, text);
+            }
+        }
+
+        vga.sync_screen().await;
+        self.dirty = false;
+    }
+
+    // puts the buttons' text on the char buffer, for when something else has the pixels
+    pub fn write_labels(&self, vga: &mut VgaDisplay) {
+        for node in &self.nodes {
+            if let Widget::Button { text } = &node.widget {
+                let rect = node.rect;
+                let col = (rect.x + rect.width / 2) / CHAR_SIZE;
+                let row = (rect.y + rect.height / 2) / CHAR_SIZE;
+
+                vga.write_text(col.saturating_sub(text.len() / 2), row, text);
+            }
+        }
+    }
+
+    fn target(&self, x: usize, y: usize) -> Option<Target> {
+        if let Some(dialog) = &self.dialog {
+            let (_, buttons) = dialog_rects(dialog);
+            return buttons
+                .iter()
+                .position(|button| contains(*button, x, y))
+                .map(Target::DialogButton);
+        }
+
+        let (i, node) = self
+            .nodes
+            .iter()
+            .enumerate()
+            .find(|(_, node)| contains(node.rect, x, y))?;
+
+        match &node.widget {
+            Widget::Button { .. } => Some(Target::Node(i)),
+            Widget::List { items, first } => {
+                let (rows, paged) = list_rows(node.rect, items.len());
+                let (back, forward) = page_rects(node.rect, rows);
+
+                if paged && contains(back, x, y) {
+                    Some(Target::PageBack(i))
+                } else if paged && contains(forward, x, y) {
+                    Some(Target::PageForward(i))
+                } else {
+                    let row = (y - node.rect.y) / ROW_HEIGHT;
+                    (row < rows && first + row < items.len())
+                        .then_some(Target::Item(i, first + row))
+                }
+            }
+            Widget::Label { .. } | Widget::Progress { .. } => None,
+        }
+    }
+
+    // acts on a tap at these vga coordinates
+    pub fn tap(&mut self, (x, y): (usize, usize)) -> Option<Event> {
+        match self.target(x, y)? {
+            Target::Node(i) => Some(Event::Pressed(WidgetId(i))),
+            Target::Item(i, item) => Some(Event::Selected(WidgetId(i), item)),
+            Target::PageBack(i) => {
+                self.turn_page(i, false);
+                None
+            }
+            Target::PageForward(i) => {
+                self.turn_page(i, true);
+                None
+            }
+            Target::DialogButton(button) => {
+                self.dialog = None;
+                self.dirty = true;
+
+                Some(Event::Dismissed(button))
+            }
+        }
+    }
+
+    // the button at these vga coordinates, without acting on the tap
+    pub fn button_at(&self, (x, y): (usize, usize)) -> Option<WidgetId> {
+        match self.target(x, y)? {
+            Target::Node(i) => Some(WidgetId(i)),
+            _ => None,
+        }
+    }
+
+    // swiping across a list pages through it, like the paging buttons do
+    pub fn swipe(&mut self, (x, y): (usize, usize), direction: Direction) {
+        if self.dialog.is_some() {
+            return;
+        }
+
+        let Some(i) = self.nodes.iter().position(|node| {
+            contains(node.rect, x, y) && matches!(node.widget, Widget::List { .. })
+        }) else {
+            return;
+        };
+
+        match direction {
+            Direction::Left => self.turn_page(i, true),
+            Direction::Right => self.turn_page(i, false),
+            Direction::Up | Direction::Down => {}
+        }
+    }
+
+    fn turn_page(&mut self, i: usize, forward: bool) {
+        let Node {
+            rect,
+            widget: Widget::List { items, first },
+        } = &mut self.nodes[i]
+        else {
+            unreachable!();
+        };
+        let (rows, paged) = list_rows(*rect, items.len());
+        if !paged {
+            return;
+        }
+
+        *first = if !forward {
+            first.saturating_sub(rows)
+        } else if *first + rows < items.len() {
+            *first + rows
+        } else {
+            *first
+        };
+        self.dirty = true;
+    }
+
+    // acts on a gesture, although it may only have changed the screen
+    pub fn handle(&mut self, gesture: Gesture) -> Option<Event> {
+        match gesture {
+            Gesture::Tap(point) => self.tap(point),
+            Gesture::Swipe {
+                from, direction, ..
+            } => {
+                self.swipe(from, direction);
+                None
+            }
+            _ => None,
+        }
+    }
+}
+
+// how many items a list shows at once, and whether it needs buttons to page through them
+fn list_rows(rect: Rect, len: usize) -> (usize, bool) {
+    let rows = rect.height / ROW_HEIGHT;
+    if len <= rows {
+        (rows, false)
+    } else {
+        (rows - 1, true)
+    }
+}
+
+fn row_rect(list: Rect, row: usize) -> Rect {
+    Rect {
+        y: list.y + row * ROW_HEIGHT,
+        height: ROW_HEIGHT - SPACING / 2,
+        ..list
+    }
+}
+
+// the paging buttons go side by side under the items
+fn page_rects(list: Rect, rows: usize) -> (Rect, Rect) {
+    let row = row_rect(list, rows);
+    let half = (row.width - SPACING) / 2;
+
+    (
+        Rect { width: half, ..row },
+        Rect {
+            x: row.x + row.width - half,
+            width: half,
+            ..row
+        },
+    )
+}
+
+// the dialog's box and its buttons, in the middle of the screen
+fn dialog_rects(dialog: &Dialog) -> (Rect, Vec<Rect>) {
+    let message_height = text_height(&dialog.message, DIALOG_WIDTH - 2 * PADDING, TEXT_SCALE);
+    let height = PADDING + message_height + SPACING + BUTTON_HEIGHT + PADDING;
+    let rect = Rect {
+        x: (IMAGE_WIDTH - DIALOG_WIDTH) / 2,
+        y: IMAGE_HEIGHT.saturating_sub(height) / 2,
+        width: DIALOG_WIDTH,
+        height: height.min(IMAGE_HEIGHT),
+    };
+
+    let count = dialog.buttons.len();
+    let width = (DIALOG_WIDTH - 2 * PADDING - (count - 1) * SPACING) / count;
+    let buttons = (0..count)
+        .map(|i| Rect {
+            x: rect.x + PADDING + i * (width + SPACING),
+            y: rect.y + rect.height - PADDING - BUTTON_HEIGHT,
+            width,
+            height: BUTTON_HEIGHT,
+        })
+        .collect();
+
+    (rect, buttons)
+}
+
+fn draw_button(vga: &mut VgaDisplay, rect: Rect, text: &str) {
+    fill(vga, rect, ACCENT);
+
+    let cell = GLYPH_SIZE * TEXT_SCALE;
+    vga.draw_text(
+        rect.x,
+        rect.y + rect.height.saturating_sub(cell) / 2,
+        rect.width,
+        text,
+        &TextStyle {
+            scale: TEXT_SCALE,
+            color: FOREGROUND,
+            align: Align::Center,
+            ..Default::default()
+        },
+    );
+}
+
+fn full_screen() -> Rect {
+    Rect {
+        x: 0,
+        y: 0,
+        width: IMAGE_WIDTH,
+        height: IMAGE_HEIGHT,
+    }
+}
+
+fn fill(vga: &mut VgaDisplay, rect: Rect, color: u16) {
+    vga.draw_box(
+        rect.x,
+        rect.y,
+        rect.x + rect.width - 1,
+        rect.y + rect.height - 1,
+        color,
+    );
+}
+
+fn inset(rect: Rect, by: usize) -> Rect {
+    Rect {
+        x: rect.x + by,
+        y: rect.y + by,
+        width: rect.width - 2 * by,
+        height: rect.height - 2 * by,
+    }
+}
+
+fn contains(rect: Rect, x: usize, y: usize) -> bool {
+    (rect.x..rect.x + rect.width).contains(&x) && (rect.y..rect.y + rect.height).contains(&y)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_paging() {
+        let mut screen = Screen::new();
+        screen.label("Pick a workout", TextStyle::default());
+        let items: Vec<_> = (1..=12).map(|i| format!("Exercise {i}")).collect();
+        let list = screen.list(items);
+
+        // 6 rows fit under the label, so one is taken up by the paging buttons
+        let first = (20, 30);
+        assert_eq!(screen.tap(first), Some(Event::Selected(list, 0)));
+
+        let forward = (300, 30 + 5 * ROW_HEIGHT);
+        assert_eq!(screen.tap(forward), None);
+        assert_eq!(screen.tap(first), Some(Event::Selected(list, 5)));
+
+        // the last page isn't full, and there's nothing after it
+        screen.tap(forward);
+        screen.tap(forward);
+        assert_eq!(screen.tap(first), Some(Event::Selected(list, 10)));
+        let empty = (20, 30 + 2 * ROW_HEIGHT);
+        assert_eq!(screen.tap(empty), None);
+
+        let back = (20, 30 + 5 * ROW_HEIGHT);
+        screen.tap(back);
+        assert_eq!(screen.tap(first), Some(Event::Selected(list, 5)));
+
+        // swiping pages too
+        screen.swipe(first, Direction::Left);
+        assert_eq!(screen.tap(first), Some(Event::Selected(list, 10)));
+    }
+
+    #[test]
+    fn test_dialog() {
+        let mut screen = Screen::new();
+        let button = screen.button("Start");
+        let middle = (IMAGE_WIDTH / 2, PADDING + 1);
+        assert_eq!(screen.tap(middle), Some(Event::Pressed(button)));
+        assert_eq!(screen.button_at(middle), Some(button));
+
+        screen.show_dialog("Connection lost", &["Retry", "Cancel"]);
+        assert_eq!(screen.tap(middle), None);
+
+        let (_, buttons) = dialog_rects(screen.dialog.as_ref().unwrap());
+        let cancel = (buttons[1].x + 1, buttons[1].y + 1);
+        assert_eq!(screen.tap(cancel), Some(Event::Dismissed(1)));
+        assert_eq!(screen.tap(middle), Some(Event::Pressed(button)));
+    }
+}
@@ -1,3 +1,8 @@
+use std::time::Duration;
+
 pub const IMAGE_WIDTH: usize = 320;
 pub const IMAGE_HEIGHT: usize = 240;
 pub const IMAGE_SIZE: usize = IMAGE_HEIGHT * IMAGE_WIDTH * std::mem::size_of::<u16>();
+
+// the device stops recording on its own after this long
+pub const MAX_VIDEO_LENGTH: Duration = Duration::from_secs(5 * 60); // 5 minutes
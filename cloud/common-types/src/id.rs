@@ -34,3 +34,4 @@ macro_rules! impl_id {
 impl_id!(UserId);
 impl_id!(DeviceId);
 impl_id!(VideoId);
+impl_id!(SessionId);
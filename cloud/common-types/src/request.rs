@@ -1,7 +1,7 @@
 use derivative::Derivative;
 use serde::{Deserialize, Serialize};
 
-use crate::{id::DeviceId, UserId};
+use crate::{id::DeviceId, SessionId, UserId};
 
 use super::workout::WorkoutType;
 
@@ -11,15 +11,19 @@ use super::workout::WorkoutType;
 pub enum LinkRequest {
     Connect { device_id: DeviceId },
     Disconnect,
+    // commands relayed to the connected device
+    SelectWorkout { workout_type: WorkoutType },
+    StartRecording,
+    StopRecording,
 }
 
-// #[derive(Serialize, Deserialize, Derivative)]
-// #[derivative(Debug)]
-// #[serde(rename_all = "snake_case")]
-// pub struct DeviceRequest {
-//     pub session_id: SessionId,
-//     pub req: VideoRequest,
-// }
+// the session ID is generated by the device, one per video
+#[derive(Serialize, Deserialize, Debug)]
+#[serde(rename_all = "snake_case")]
+pub struct DeviceRequest {
+    pub session_id: SessionId,
+    pub req: VideoRequest,
+}
 
 #[derive(Serialize, Deserialize, Derivative)]
 #[derivative(Debug)]
@@ -31,7 +35,7 @@ pub enum VideoRequest {
     },
     Frames(#[derivative(Debug = "ignore")] Vec<Frame>),
     Done,
-    Cancel, // drop whatever video is currently being handled, if any
+    Cancel, // drop the video for this session, if it is still being handled
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
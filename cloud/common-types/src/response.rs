@@ -1,6 +1,9 @@
 use serde::{Deserialize, Serialize};
 
-use super::id::{DeviceId, UserId};
+use super::{
+    id::{DeviceId, SessionId, UserId},
+    workout::WorkoutType,
+};
 
 #[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
 #[serde(rename_all = "snake_case")]
@@ -15,6 +18,50 @@ pub enum UserResponse {
 #[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
 #[serde(rename_all = "snake_case")]
 pub enum DeviceResponse {
-    Connected { user_id: UserId },
+    Connected {
+        user_id: UserId,
+    },
     Disconnected,
+    // commands relayed from the connected user
+    SelectWorkout {
+        workout_type: WorkoutType,
+    },
+    StartRecording,
+    StopRecording,
+    // something went wrong with one of the device's videos
+    // if it isn't recoverable, the video was dropped
+    Error {
+        // which video it was, if that could be made out
+        session_id: Option<SessionId>,
+        code: ErrorCode,
+        message: String,
+        recoverable: bool,
+    },
+    // something the device should let the user know about, like by beeping
+    Cue {
+        session_id: SessionId,
+        cue: Cue,
+    },
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum Cue {
+    // the video being recorded will be stopped soon
+    NearLimit,
+    // feedback for the video is ready
+    Analyzed,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum ErrorCode {
+    InvalidState,
+    MessageTooLarge,
+    TooManyFrames,
+    BadFrame,
+    VideoTooLong,
+    BandwidthExceeded,
+    StorageFailure,
+    AnalyzerFailure,
 }
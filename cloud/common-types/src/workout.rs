@@ -1,15 +1,140 @@
 use serde::{Deserialize, Serialize};
 
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Serialize, Deserialize, Clone)]
 #[allow(unused)]
 pub struct Feedback {
     pub class: String,
     pub correction: String,
+    // out of 100, for analyzers that report it
+    #[serde(default)]
+    pub confidence: Option<f64>,
+    // where in the video the rep happened, as frame indices
+    #[serde(default)]
+    pub start_frame: Option<u32>,
+    #[serde(default)]
+    pub bottom_frame: Option<u32>,
+    #[serde(default)]
+    pub end_frame: Option<u32>,
+    #[serde(default)]
+    pub metrics: Option<RepMetrics>,
+    // how closely the rep followed the reference rep, out of 100
+    #[serde(default)]
+    pub form_score: Option<f64>,
+    // where the rep strayed furthest from the reference rep
+    #[serde(default)]
+    pub worst_phase: Option<RepPhase>,
 }
 
-#[derive(Serialize, Deserialize, Clone, Copy, Debug)]
+/// Part of a rep, going by where the reference rep bottoms out.
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum RepPhase {
+    Descent,
+    Bottom,
+    Ascent,
+}
+
+/// Measurements of a single rep, with angles in degrees.
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq)]
+pub struct RepMetrics {
+    /// Time spent going down.
+    pub eccentric_secs: f64,
+    /// Time spent coming back up.
+    pub concentric_secs: f64,
+    /// Smallest knee angle during the rep, lower is deeper.
+    pub min_knee_angle: f64,
+    /// Average of both hip angles at the bottom of the rep.
+    pub hip_angle_at_bottom: Option<f64>,
+    /// Average difference between the left and right knee angles.
+    pub asymmetry: f64,
+}
+
+/// Averages of `RepMetrics` over a workout.
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq)]
+pub struct WorkoutMetrics {
+    /// Reps that the averages are taken over.
+    pub rep_count: u32,
+    pub mean_eccentric_secs: f64,
+    pub mean_concentric_secs: f64,
+    pub mean_min_knee_angle: f64,
+    pub deepest_knee_angle: f64,
+    pub mean_hip_angle_at_bottom: Option<f64>,
+    pub mean_asymmetry: f64,
+}
+
+/// Something about the set as a whole, like form breaking down towards the end.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct SetInsight {
+    /// Last rep before form broke down, counting from 1.
+    pub breakdown_after: Option<u32>,
+    /// How much shallower the reps got over the set, in degrees.
+    pub depth_loss: Option<f64>,
+    /// How much slower the reps came up by the end of the set, as a fraction.
+    pub slowdown: Option<f64>,
+    /// What to show the user.
+    pub message: String,
+}
+
+/// Feedback for an exercise that is held rather than repeated.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct HoldFeedback {
+    /// Seconds from first getting into position to last being in it.
+    pub hold_secs: f64,
+    /// Seconds of the hold spent in good alignment.
+    pub aligned_secs: f64,
+    /// Stretches where the alignment slipped, in order.
+    pub faults: Vec<HoldFault>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct HoldFault {
+    pub kind: HoldFaultKind,
+    pub start_frame: u32,
+    pub end_frame: u32,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum HoldFaultKind {
+    /// Hips dropped below the line from the shoulders to the ankles.
+    HipsSagged,
+    /// Hips raised above it.
+    HipsPiked,
+}
+
+/// How an exercise is measured.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ExerciseKind {
+    /// Counted in reps, each getting its own `Feedback`.
+    Reps,
+    /// Held for as long as possible, getting a `HoldFeedback`.
+    Duration,
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
 #[serde(rename_all = "snake_case")]
 pub enum WorkoutType {
     Squat,
     Pushup,
+    Plank,
+}
+
+impl WorkoutType {
+    // in the order the device offers them
+    pub const ALL: [Self; 3] = [Self::Squat, Self::Pushup, Self::Plank];
+
+    pub fn kind(self) -> ExerciseKind {
+        match self {
+            Self::Squat | Self::Pushup => ExerciseKind::Reps,
+            Self::Plank => ExerciseKind::Duration,
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Squat => "Squat",
+            Self::Pushup => "Push-up",
+            Self::Plank => "Plank",
+        }
+    }
 }
@@ -0,0 +1,177 @@
+use std::{collections::VecDeque, f64::consts::TAU, io, mem::size_of, time::Duration};
+
+use tokio::sync::mpsc::{self, UnboundedReceiver, UnboundedSender};
+
+use crate::{devmem::Region, DevMem, LW_BRIDGE_BASE};
+
+// not in our Computer_System.qsys, this is where address_map_arm.h puts the audio port
+pub(crate) const AUDIO_BASE: u64 = LW_BRIDGE_BASE + 0x3040;
+const AUDIO_SPAN: usize = size_of::<u32>() * 4;
+
+pub(crate) const CONTROL_OFFSET: usize = 0x0;
+// how much room is left in each fifo
+pub(crate) const FIFOSPACE_OFFSET: usize = 0x4;
+pub(crate) const LEFT_DATA_OFFSET: usize = 0x8;
+pub(crate) const RIGHT_DATA_OFFSET: usize = 0xC;
+
+// empties the output fifos while set
+pub(crate) const CLEAR_WRITE: u32 = 1 << 3;
+
+// what AV_Config sets the codec up for
+pub const SAMPLE_RATE: u32 = 8000;
+pub(crate) const FIFO_SIZE: u32 = 128;
+
+// the fifo only holds 16 ms, so it has to be topped up well before then
+const POLL_RATE: Duration = Duration::from_millis(4);
+// loud enough to hear across a room, with room to spare before clipping
+const VOLUME: f64 = i32::MAX as f64 / 4.0;
+// ramps at either end of a tone, so it doesn't click
+const FADE_TIME: f64 = 0.005;
+
+enum Command {
+    Play(Vec<i32>),
+    Stop,
+}
+
+// the line out, which plays sounds one after another in the background
+#[derive(Clone)]
+pub struct AudioOut {
+    tx: UnboundedSender<Command>,
+}
+
+impl AudioOut {
+    // has to be called from inside the tokio runtime, which the sound is played from
+    pub fn new(mem: &DevMem) -> io::Result<Self> {
+        let port = mem.map(AUDIO_BASE, AUDIO_SPAN)?;
+        clear(&port);
+
+        let (tx, rx) = mpsc::unbounded_channel();
+        tokio::spawn(play_loop(port, rx));
+
+        Ok(Self { tx })
+    }
+
+    // for a board without an audio port, anything played is thrown away
+    pub fn silent() -> Self {
+        let (tx, _) = mpsc::unbounded_channel();
+
+        Self { tx }
+    }
+
+    // mono samples, which start once everything before them has played
+    pub fn play(&self, samples: Vec<i32>) {
+        // the loop only stops when every sender is gone
+        _ = self.tx.send(Command::Play(samples));
+    }
+
+    pub fn tone(&self, frequency: f64, duration: Duration) {
+        self.play(tone(frequency, duration));
+    }
+
+    // cuts off whatever is playing, and anything waiting to
+    pub fn stop(&self) {
+        _ = self.tx.send(Command::Stop);
+    }
+}
+
+// a sine wave, or silence for a frequency of 0
+pub fn tone(frequency: f64, duration: Duration) -> Vec<i32> {
+    let len = (duration.as_secs_f64() * SAMPLE_RATE as f64) as usize;
+    let fade = FADE_TIME * SAMPLE_RATE as f64;
+
+    (0..len)
+        .map(|i| {
+            let t = i as f64 / SAMPLE_RATE as f64;
+            let envelope = (i.min(len - 1 - i) as f64 / fade).min(1.0);
+
+            ((TAU * frequency * t).sin() * envelope * VOLUME) as i32
+        })
+        .collect()
+}
+
+fn clear(port: &Region) {
+    let control = port.reg(CONTROL_OFFSET);
+    control.write(control.read() | CLEAR_WRITE);
+    control.write(control.read() & !CLEAR_WRITE);
+}
+
+async fn play_loop(port: Region, mut rx: UnboundedReceiver<Command>) {
+    let mut queue = VecDeque::new();
+
+    loop {
+        if queue.is_empty() {
+            match rx.recv().await {
+                Some(Command::Play(samples)) => queue.extend(samples),
+                Some(Command::Stop) => {}
+                None => return,
+            }
+        }
+
+        // anything sent while playing
+        while let Ok(command) = rx.try_recv() {
+            match command {
+                Command::Play(samples) => queue.extend(samples),
+                Command::Stop => {
+                    queue.clear();
+                    clear(&port);
+                }
+            }
+        }
+
+        // the same samples go to both channels, so whichever has less room
+        let space = port.reg(FIFOSPACE_OFFSET).read();
+        let room = (space >> 24).min(space >> 16 & 0xFF);
+        for sample in queue.drain(..queue.len().min(room as usize)) {
+            port.reg(LEFT_DATA_OFFSET).write(sample as u32);
+            port.reg(RIGHT_DATA_OFFSET).write(sample as u32);
+        }
+
+        tokio::time::sleep(POLL_RATE).await;
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::Simulator;
+
+    #[tokio::test]
+    async fn test_tone() -> io::Result<()> {
+        let sim = Simulator::new()?;
+        let audio = AudioOut::new(&DevMem::simulated(sim.clone()))?;
+
+        audio.tone(1000.0, Duration::from_millis(100));
+        tokio::time::sleep(Duration::from_millis(300)).await;
+        let played = sim.take_audio();
+        assert_eq!(played.len(), SAMPLE_RATE as usize / 10);
+
+        // quiet at either end, and full volume in the middle
+        let fade = (FADE_TIME * SAMPLE_RATE as f64) as usize;
+        let loudest = |samples: &[i32]| samples.iter().map(|s| s.unsigned_abs()).max().unwrap();
+        assert!((loudest(&played[..fade / 4]) as f64) < VOLUME / 4.0);
+        assert!((loudest(&played[played.len() - fade / 4..]) as f64) < VOLUME / 4.0);
+        assert!((loudest(&played[fade..played.len() - fade]) as f64) > VOLUME * 0.99);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_stop() -> io::Result<()> {
+        let sim = Simulator::new()?;
+        let audio = AudioOut::new(&DevMem::simulated(sim.clone()))?;
+
+        audio.tone(1000.0, Duration::from_secs(2));
+        audio.tone(500.0, Duration::from_secs(2));
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        audio.stop();
+        tokio::time::sleep(Duration::from_millis(50)).await;
+
+        // what was already in the fifo, but nothing after it
+        let played = sim.take_audio();
+        assert!(played.len() < SAMPLE_RATE as usize / 5, "{}", played.len());
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        assert_eq!(sim.take_audio(), []);
+
+        Ok(())
+    }
+}
@@ -0,0 +1,120 @@
+use std::{io, path::Path};
+
+use common_types::{IMAGE_HEIGHT, IMAGE_WIDTH};
+
+use crate::{TOUCHSCREEN_HEIGHT, TOUCHSCREEN_WIDTH};
+
+// where to ask the user to touch, in vga coordinates
+// spread out over the screen, but not so close to the edges that they're hard to hit
+pub const CALIBRATION_TARGETS: [(usize, usize); 3] = [(32, 24), (288, 120), (160, 216)];
+
+// maps raw touchscreen coordinates to vga coordinates, which corrects for the panel being
+// shifted, stretched or rotated a little relative to the display
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Calibration {
+    // vga x and y, each from raw x, raw y and 1
+    matrix: [[f64; 3]; 2],
+}
+
+impl Default for Calibration {
+    // what we assumed before calibrating, that the panel lines up exactly with the display
+    fn default() -> Self {
+        Self {
+            matrix: [
+                [IMAGE_WIDTH as f64 / TOUCHSCREEN_WIDTH as f64, 0.0, 0.0],
+                [0.0, IMAGE_HEIGHT as f64 / TOUCHSCREEN_HEIGHT as f64, 0.0],
+            ],
+        }
+    }
+}
+
+impl Calibration {
+    // from where each of the targets was touched, in raw coordinates
+    // gives up if the touches were in a line, since that can't tell us anything about the other direction
+    pub fn from_touches(touches: [(usize, usize); 3]) -> Option<Self> {
+        let [(x0, y0), (x1, y1), (x2, y2)] = touches.map(|(x, y)| (x as f64, y as f64));
+
+        let determinant = x0 * (y1 - y2) - y0 * (x1 - x2) + (x1 * y2 - x2 * y1);
+        // twice the area of the triangle the touches make, so a thin one is as good as a line
+        if determinant.abs() < (TOUCHSCREEN_WIDTH * 16) as f64 {
+            return None;
+        }
+
+        // each row on its own, with cramer's rule
+        let solve = |[t0, t1, t2]: [f64; 3]| {
+            [
+                (t0 * (y1 - y2) - y0 * (t1 - t2) + (t1 * y2 - t2 * y1)) / determinant,
+                (x0 * (t1 - t2) - t0 * (x1 - x2) + (x1 * t2 - x2 * t1)) / determinant,
+                (x0 * (y1 * t2 - y2 * t1) - y0 * (x1 * t2 - x2 * t1) + t0 * (x1 * y2 - x2 * y1))
+                    / determinant,
+            ]
+        };
+
+        let [a, b, c] = CALIBRATION_TARGETS;
+        Some(Self {
+            matrix: [
+                solve([a.0, b.0, c.0].map(|t| t as f64)),
+                solve([a.1, b.1, c.1].map(|t| t as f64)),
+            ],
+        })
+    }
+
+    // touches off the edge of the display are moved onto it
+    pub fn to_vga(&self, x: usize, y: usize) -> (usize, usize) {
+        let [vx, vy] = self.matrix.map(|[a, b, c]| a * x as f64 + b * y as f64 + c);
+
+        (
+            (vx.round().max(0.0) as usize).min(IMAGE_WIDTH - 1),
+            (vy.round().max(0.0) as usize).min(IMAGE_HEIGHT - 1),
+        )
+    }
+
+    // kept as the six numbers of the matrix on one line
+    pub fn load(path: impl AsRef<Path>) -> io::Result<Self> {
+        let text = std::fs::read_to_string(path)?;
+        let numbers = text
+            .split_whitespace()
+            .map(|n| n.parse::<f64>())
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+
+        let [a, b, c, d, e, f] = numbers[..] else {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "expected six numbers",
+            ));
+        };
+
+        Ok(Self {
+            matrix: [[a, b, c], [d, e, f]],
+        })
+    }
+
+    pub fn save(&self, path: impl AsRef<Path>) -> io::Result<()> {
+        let [[a, b, c], [d, e, f]] = self.matrix;
+        std::fs::write(path, format!("{a} {b} {c} {d} {e} {f}\n"))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_from_touches() {
+        // a panel that is flipped horizontally and shifted down
+        let raw = |(x, y): (usize, usize)| (4000 - x * 12, 100 + y * 15);
+        let calibration = Calibration::from_touches(CALIBRATION_TARGETS.map(raw)).unwrap();
+
+        for point in [(0, 0), (100, 50), (319, 239), (160, 120)] {
+            assert_eq!(calibration.to_vga(raw(point).0, raw(point).1), point);
+        }
+        // off the edge
+        assert_eq!(calibration.to_vga(4095, 0), (0, 0));
+
+        assert_eq!(
+            Calibration::from_touches([(0, 0), (100, 100), (200, 200)]),
+            None
+        );
+    }
+}
@@ -1,21 +1,27 @@
 use std::{intrinsics, io, mem::size_of};
 
 use common_types::{Frame, IMAGE_SIZE, IMAGE_WIDTH};
-use memmap2::{MmapOptions, MmapRaw};
 
-use crate::{devmem::DevMem, LW_BRIDGE_BASE};
+use crate::{
+    devmem::{DevMem, Region},
+    LW_BRIDGE_BASE,
+};
 
-const VIDEO_IN_CTRL_BASE: u64 = LW_BRIDGE_BASE + 0x0000306C;
-const VIDEO_IN_CTRL_SPAN: usize = size_of::<u32>();
+pub(crate) const VIDEO_IN_BASE: u64 = LW_BRIDGE_BASE + 0x00003060;
+const VIDEO_IN_SPAN: usize = size_of::<u32>() * 4;
 
-const BUFFER_BASE: u64 = 0xC8000000;
+// register offsets from the base
+const BUFFER_OFFSET: usize = 0;
+pub(crate) const CONTROL_OFFSET: usize = size_of::<u32>() * 3;
+
+pub(crate) const BUFFER_BASE: u64 = 0xC8000000;
 const BUFFER_SPAN: u64 = 0x0003FFFF;
 
-const CAMERA_ENABLE: u32 = 1 << 2;
+pub(crate) const CAMERA_ENABLE: u32 = 1 << 2;
 
 pub struct Camera {
-    control: MmapRaw,
-    buffer: MmapRaw,
+    control: Region,
+    buffer: Region,
 }
 
 pub struct CameraGuard<'a>(&'a mut Camera);
@@ -29,45 +35,30 @@ impl CameraGuard<'_> {
 impl Drop for CameraGuard<'_> {
     fn drop(&mut self) {
         // disable the camera
-        unsafe {
-            println!("Disabled camera");
-            let control_ptr = self.0.control.as_mut_ptr() as *mut u32;
-            control_ptr.write_volatile(control_ptr.read_volatile() & !CAMERA_ENABLE);
-        }
+        println!("Disabled camera");
+        let control = self.0.control.reg(CONTROL_OFFSET);
+        control.write(control.read() & !CAMERA_ENABLE);
     }
 }
 
 impl Camera {
-    pub fn new(DevMem(mem): &DevMem) -> io::Result<Self> {
-        let control = MmapOptions::new()
-            .offset(VIDEO_IN_CTRL_BASE)
-            .len(VIDEO_IN_CTRL_SPAN)
-            .map_raw(mem)?;
-
-        let buffer = MmapOptions::new()
-            .offset(BUFFER_BASE)
-            .len(BUFFER_SPAN as usize)
-            .map_raw(mem)?;
+    pub fn new(mem: &DevMem) -> io::Result<Self> {
+        let control = mem.map(VIDEO_IN_BASE, VIDEO_IN_SPAN)?;
+        let buffer = mem.map(BUFFER_BASE, BUFFER_SPAN as usize)?;
 
-        unsafe {
-            // make sure camera is disabled to begin with, regardless of previous state
-            let control_ptr = control.as_mut_ptr() as *mut u32;
-            let front_buf = control.as_mut_ptr().sub(2 * size_of::<u32>()) as *mut u32;
+        // make sure camera is disabled to begin with, regardless of previous state
+        println!("Camera buffer is {:x}", control.reg(BUFFER_OFFSET).read());
 
-            println!("Camera buffer is {:x}", front_buf.read_volatile());
-
-            control_ptr.write_volatile(control_ptr.read_volatile() & !CAMERA_ENABLE);
-        }
+        let control_reg = control.reg(CONTROL_OFFSET);
+        control_reg.write(control_reg.read() & !CAMERA_ENABLE);
 
         Ok(Self { control, buffer })
     }
 
     pub fn enable(&mut self) -> CameraGuard {
-        unsafe {
-            println!("Enabled camera");
-            let control_ptr = self.control.as_mut_ptr() as *mut u32;
-            control_ptr.write_volatile(control_ptr.read_volatile() | CAMERA_ENABLE);
-        }
+        println!("Enabled camera");
+        let control = self.control.reg(CONTROL_OFFSET);
+        control.write(control.read() | CAMERA_ENABLE);
 
         CameraGuard(self)
     }
@@ -1,16 +1,106 @@
-use tokio::{fs::File, io};
+use std::{io, mem::size_of, sync::Arc};
 
-pub struct DevMem(pub(super) File);
+use memmap2::{MmapOptions, MmapRaw};
+use tokio::fs::File;
+
+use crate::Simulator;
+
+pub struct DevMem(Backend);
+
+enum Backend {
+    Device(File),
+    Simulated(Arc<Simulator>),
+}
 
 impl DevMem {
     pub async fn new() -> io::Result<Self> {
-        Ok(Self(
+        Ok(Self(Backend::Device(
             tokio::fs::OpenOptions::new()
                 .read(true)
                 .write(true)
                 .custom_flags(libc::O_SYNC)
                 .open("/dev/mem")
                 .await?,
-        ))
+        )))
+    }
+
+    // peripherals opened on this act on the simulator instead of the board
+    pub fn simulated(sim: Arc<Simulator>) -> Self {
+        Self(Backend::Simulated(sim))
+    }
+
+    pub(crate) fn map(&self, base: u64, len: usize) -> io::Result<Region> {
+        match &self.0 {
+            Backend::Device(mem) => Ok(Region {
+                map: MmapOptions::new().offset(base).len(len).map_raw(mem)?,
+                base,
+                sim: None,
+            }),
+            Backend::Simulated(sim) => Ok(Region {
+                map: sim.map(base, len)?,
+                base,
+                sim: Some(sim.clone()),
+            }),
+        }
+    }
+}
+
+// a span of physical memory, whose registers may have side effects when simulated
+pub(crate) struct Region {
+    map: MmapRaw,
+    base: u64,
+    sim: Option<Arc<Simulator>>,
+}
+
+impl Region {
+    #[inline]
+    pub(crate) fn as_ptr(&self) -> *const u8 {
+        self.map.as_ptr()
+    }
+
+    #[inline]
+    pub(crate) fn as_mut_ptr(&self) -> *mut u8 {
+        self.map.as_mut_ptr()
+    }
+
+    #[inline]
+    pub(crate) fn reg(&self, offset: usize) -> Register<'_> {
+        assert!(offset + size_of::<u32>() <= self.map.len());
+
+        Register {
+            region: self,
+            offset,
+        }
+    }
+}
+
+// a 32-bit register within a region
+pub(crate) struct Register<'a> {
+    region: &'a Region,
+    offset: usize,
+}
+
+impl Register<'_> {
+    pub(crate) fn read(&self) -> u32 {
+        let Region { base, sim, .. } = self.region;
+
+        sim.as_ref()
+            .and_then(|sim| sim.read(base + self.offset as u64))
+            .unwrap_or_else(|| unsafe { self.ptr().read_volatile() })
+    }
+
+    pub(crate) fn write(&self, value: u32) {
+        let Region { base, sim, .. } = self.region;
+
+        if !sim
+            .as_ref()
+            .is_some_and(|sim| sim.write(base + self.offset as u64, value))
+        {
+            unsafe { self.ptr().write_volatile(value) }
+        }
+    }
+
+    fn ptr(&self) -> *mut u32 {
+        unsafe { self.region.as_mut_ptr().add(self.offset) as *mut u32 }
     }
 }
@@ -0,0 +1,263 @@
+use std::time::Duration;
+
+use tokio::time::Instant;
+
+use crate::{PenState, TouchScreen};
+
+// how far the pen can wander and still be a tap, in vga pixels
+const TAP_SLOP: usize = 8;
+pub(crate) const LONG_PRESS_TIME: Duration = Duration::from_millis(600);
+// a drag that covers this much ground this quickly is a swipe
+const SWIPE_TIME: Duration = Duration::from_millis(300);
+const SWIPE_DISTANCE: usize = 40;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Direction {
+    Left,
+    Right,
+    Up,
+    Down,
+}
+
+// positions are in vga coordinates
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Gesture {
+    Tap((usize, usize)),
+    // sent as soon as the pen has been held still for long enough, not when it's lifted
+    LongPress((usize, usize)),
+    // sent instead of the drag finishing
+    Swipe {
+        from: (usize, usize),
+        to: (usize, usize),
+        direction: Direction,
+    },
+    // sent each time the pen moves, then once more with `done` when it's lifted
+    Drag {
+        from: (usize, usize),
+        to: (usize, usize),
+        done: bool,
+    },
+}
+
+#[derive(Default)]
+enum State {
+    #[default]
+    Up,
+    // down, and hasn't gone far
+    Pressed {
+        at: (usize, usize),
+        since: Instant,
+    },
+    Dragging {
+        from: (usize, usize),
+        to: (usize, usize),
+        since: Instant,
+    },
+    // already a long press, so nothing else until it's lifted
+    Held,
+}
+
+// turns touches into gestures
+// keep the same one around between calls, so a gesture isn't lost when waiting for one is cancelled
+#[derive(Default)]
+pub struct Gestures {
+    state: State,
+}
+
+impl Gestures {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub async fn next(&mut self, touch: &TouchScreen) -> Gesture {
+        loop {
+            let event = match self.state {
+                State::Pressed { since, .. } => {
+                    let deadline = since + LONG_PRESS_TIME;
+                    match tokio::time::timeout_at(deadline, touch.read()).await {
+                        Ok(event) => event,
+                        Err(_) => {
+                            if let Some(gesture) = self.on_timeout(Instant::now()) {
+                                return gesture;
+                            }
+                            continue;
+                        }
+                    }
+                }
+                _ => touch.read().await,
+            };
+
+            let point = touch.to_vga(&event);
+            if let Some(gesture) = self.on_touch(point, event.pen_state, Instant::now()) {
+                return gesture;
+            }
+        }
+    }
+
+    pub(crate) fn on_touch(
+        &mut self,
+        point: (usize, usize),
+        pen_state: PenState,
+        now: Instant,
+    ) -> Option<Gesture> {
+        let (state, gesture) = match (std::mem::take(&mut self.state), pen_state) {
+            (State::Up, PenState::Down) => (
+                State::Pressed {
+                    at: point,
+                    since: now,
+                },
+                None,
+            ),
+            (State::Up, PenState::Up) => (State::Up, None),
+            (State::Pressed { at, since }, PenState::Down) => {
+                if distance(at, point) > TAP_SLOP {
+                    let gesture = Gesture::Drag {
+                        from: at,
+                        to: point,
+                        done: false,
+                    };
+                    (
+                        State::Dragging {
+                            from: at,
+                            to: point,
+                            since,
+                        },
+                        Some(gesture),
+                    )
+                } else {
+                    (State::Pressed { at, since }, None)
+                }
+            }
+            (State::Pressed { at, .. }, PenState::Up) => (State::Up, Some(Gesture::Tap(at))),
+            (State::Dragging { from, to, since }, PenState::Down) => {
+                // the panel keeps reporting while the pen is still
+                let gesture = (point != to).then_some(Gesture::Drag {
+                    from,
+                    to: point,
+                    done: false,
+                });
+                (
+                    State::Dragging {
+                        from,
+                        to: point,
+                        since,
+                    },
+                    gesture,
+                )
+            }
+            (State::Dragging { from, since, .. }, PenState::Up) => {
+                let gesture =
+                    if now - since <= SWIPE_TIME && distance(from, point) >= SWIPE_DISTANCE {
+                        Gesture::Swipe {
+                            from,
+                            to: point,
+                            direction: direction(from, point),
+                        }
+                    } else {
+                        Gesture::Drag {
+                            from,
+                            to: point,
+                            done: true,
+                        }
+                    };
+                (State::Up, Some(gesture))
+            }
+            (State::Held, PenState::Down) => (State::Held, None),
+            (State::Held, PenState::Up) => (State::Up, None),
+        };
+
+        self.state = state;
+        gesture
+    }
+
+    // a long press, if the pen has been down in one place for long enough
+    pub(crate) fn on_timeout(&mut self, now: Instant) -> Option<Gesture> {
+        let State::Pressed { at, since } = self.state else {
+            return None;
+        };
+        if now - since < LONG_PRESS_TIME {
+            return None;
+        }
+
+        self.state = State::Held;
+        Some(Gesture::LongPress(at))
+    }
+}
+
+fn distance((x0, y0): (usize, usize), (x1, y1): (usize, usize)) -> usize {
+    x0.abs_diff(x1).max(y0.abs_diff(y1))
+}
+
+// whichever way it went furthest
+fn direction((x0, y0): (usize, usize), (x1, y1): (usize, usize)) -> Direction {
+    if x0.abs_diff(x1) >= y0.abs_diff(y1) {
+        if x1 < x0 {
+            Direction::Left
+        } else {
+            Direction::Right
+        }
+    } else if y1 < y0 {
+        Direction::Up
+    } else {
+        Direction::Down
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_gestures() {
+        let mut gestures = Gestures::new();
+        let start = Instant::now();
+        let at = |ms| start + Duration::from_millis(ms);
+
+        // wobbling a little is still a tap
+        assert_eq!(gestures.on_touch((100, 100), PenState::Down, at(0)), None);
+        assert_eq!(gestures.on_touch((104, 97), PenState::Down, at(50)), None);
+        assert_eq!(
+            gestures.on_touch((104, 97), PenState::Up, at(100)),
+            Some(Gesture::Tap((100, 100)))
+        );
+
+        gestures.on_touch((100, 100), PenState::Down, at(1000));
+        assert_eq!(gestures.on_timeout(at(1500)), None);
+        assert_eq!(
+            gestures.on_timeout(at(1600)),
+            Some(Gesture::LongPress((100, 100)))
+        );
+        assert_eq!(gestures.on_touch((100, 100), PenState::Up, at(2000)), None);
+
+        gestures.on_touch((200, 100), PenState::Down, at(3000));
+        assert_eq!(
+            gestures.on_touch((150, 110), PenState::Down, at(3100)),
+            Some(Gesture::Drag {
+                from: (200, 100),
+                to: (150, 110),
+                done: false
+            })
+        );
+        assert_eq!(gestures.on_timeout(at(4000)), None);
+        assert_eq!(
+            gestures.on_touch((100, 110), PenState::Up, at(3200)),
+            Some(Gesture::Swipe {
+                from: (200, 100),
+                to: (100, 110),
+                direction: Direction::Left
+            })
+        );
+
+        // too slow to be a swipe
+        gestures.on_touch((100, 50), PenState::Down, at(4000));
+        gestures.on_touch((100, 100), PenState::Down, at(4500));
+        assert_eq!(
+            gestures.on_touch((100, 150), PenState::Up, at(5000)),
+            Some(Gesture::Drag {
+                from: (100, 50),
+                to: (100, 150),
+                done: true
+            })
+        );
+    }
+}
@@ -1,23 +1,18 @@
 use std::io;
 
-use memmap2::{MmapOptions, MmapRaw};
+use crate::{devmem::Region, DevMem, LW_BRIDGE_BASE};
 
-use crate::{DevMem, LW_BRIDGE_BASE};
-
-const HEX_BASE: u64 = LW_BRIDGE_BASE + 0x20;
+pub(crate) const HEX_BASE: u64 = LW_BRIDGE_BASE + 0x20;
 const HEX_SPAN: usize = 0x20;
-const HEX_HIGH_OFFSET: usize = 0x10;
+pub(crate) const HEX_HIGH_OFFSET: usize = 0x10;
 
 pub struct HexDisplay {
-    hex: MmapRaw,
+    hex: Region,
 }
 
 impl HexDisplay {
-    pub fn new(DevMem(mem): &DevMem) -> io::Result<Self> {
-        let hex = MmapOptions::new()
-            .offset(HEX_BASE)
-            .len(HEX_SPAN)
-            .map_raw(mem)?;
+    pub fn new(mem: &DevMem) -> io::Result<Self> {
+        let hex = mem.map(HEX_BASE, HEX_SPAN)?;
 
         let mut res = Self { hex };
 
@@ -28,12 +23,12 @@ impl HexDisplay {
     }
 
     pub fn write(&mut self, data: [u8; 6]) {
-        unsafe {
-            (self.hex.as_mut_ptr() as *mut u32)
-                .write_volatile(u32::from_be_bytes([data[2], data[3], data[4], data[5]]));
-            (self.hex.as_mut_ptr().add(HEX_HIGH_OFFSET) as *mut u32)
-                .write_volatile(u32::from_be_bytes([0, 0, data[0], data[1]]));
-        }
+        self.hex
+            .reg(0)
+            .write(u32::from_be_bytes([data[2], data[3], data[4], data[5]]));
+        self.hex
+            .reg(HEX_HIGH_OFFSET)
+            .write(u32::from_be_bytes([0, 0, data[0], data[1]]));
     }
 
     pub fn clear(&mut self) {
@@ -0,0 +1,151 @@
+use std::{collections::VecDeque, time::Duration};
+
+use tokio::time::{Instant, Interval, MissedTickBehavior};
+
+use crate::{
+    gesture::LONG_PRESS_TIME, touchscreen::Packet, Gesture, Gestures, KeyboardEvent, Keys,
+    KeysPressed, Ps2Keyboard, Switches, SwitchesOn, TouchScreen,
+};
+
+// none of these can tell us when something happens, so they're all checked this often
+const POLL_RATE: Duration = Duration::from_millis(10);
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum InputEvent {
+    KeyDown(usize),
+    KeyUp(usize),
+    // sent while the key is still held, before it goes up
+    KeyLongPress(usize),
+    Touch(Gesture),
+    Switch { index: usize, on: bool },
+    Keyboard(KeyboardEvent),
+}
+
+// everything the user can do to the board, as one stream of events
+pub struct Input {
+    keys: Keys,
+    touch: TouchScreen,
+    switches: Switches,
+    // only if the board has a PS/2 port
+    keyboard: Option<Ps2Keyboard>,
+    interval: Interval,
+    // what's happened that hasn't been asked for yet
+    events: VecDeque<InputEvent>,
+    // when each key went down, until it goes back up or counts as a long press
+    keys_down_since: [Option<Instant>; 4],
+    // which keys count as down, which only the edge register can undo
+    keys_held: KeysPressed,
+    switches_on: SwitchesOn,
+    packet: Packet,
+    gestures: Gestures,
+}
+
+impl Input {
+    pub fn new(
+        mut keys: Keys,
+        touch: TouchScreen,
+        switches: Switches,
+        keyboard: Option<Ps2Keyboard>,
+    ) -> Self {
+        let mut interval = tokio::time::interval(POLL_RATE);
+        interval.set_missed_tick_behavior(MissedTickBehavior::Delay);
+        // anything let go before now is old news
+        keys.take_released();
+        let keys_held = keys.held();
+        let switches_on = switches.read();
+
+        Self {
+            keys,
+            touch,
+            switches,
+            keyboard,
+            interval,
+            events: VecDeque::new(),
+            keys_down_since: [None; 4],
+            keys_held,
+            switches_on,
+            packet: Packet::default(),
+            gestures: Gestures::new(),
+        }
+    }
+
+    // nothing is lost if this is cancelled
+    pub async fn next(&mut self) -> InputEvent {
+        loop {
+            if let Some(event) = self.events.pop_front() {
+                return event;
+            }
+
+            self.interval.tick().await;
+            self.poll();
+        }
+    }
+
+    // drops everything that's happened so far, e.g. before showing a new screen
+    pub fn flush(&mut self) {
+        self.poll();
+        self.events.clear();
+    }
+
+    pub fn switches(&self) -> SwitchesOn {
+        self.switches_on
+    }
+
+    fn poll(&mut self) {
+        let now = Instant::now();
+
+        // releases first, so that one between the two reads shows up as a release next time
+        // rather than as the key still being down
+        let released = self.keys.take_released();
+        let keys_held = self.keys.held();
+        for i in 0..keys_held.len() {
+            let since = &mut self.keys_down_since[i];
+            let mut down = self.keys_held[i];
+
+            // only the edge register says when a key goes up, since a quick tap can
+            // come and go between polls without the key ever being seen down
+            if released[i] {
+                if !down {
+                    self.events.push_back(InputEvent::KeyDown(i));
+                }
+                *since = None;
+                down = false;
+                self.events.push_back(InputEvent::KeyUp(i));
+            }
+
+            if keys_held[i] && !down {
+                *since = Some(now);
+                down = true;
+                self.events.push_back(InputEvent::KeyDown(i));
+            } else if down && since.is_some_and(|since| now - since >= LONG_PRESS_TIME) {
+                *since = None;
+                self.events.push_back(InputEvent::KeyLongPress(i));
+            }
+
+            self.keys_held[i] = down;
+        }
+
+        let switches_on = self.switches.read();
+        for (index, (&was, &on)) in self.switches_on.iter().zip(&switches_on).enumerate() {
+            if was != on {
+                self.events.push_back(InputEvent::Switch { index, on });
+            }
+        }
+        self.switches_on = switches_on;
+
+        if let Some(keyboard) = &mut self.keyboard {
+            let events = keyboard.read().into_iter().map(InputEvent::Keyboard);
+            self.events.extend(events);
+        }
+
+        while let Some(event) = self.touch.try_read(&mut self.packet) {
+            let point = self.touch.to_vga(&event);
+            if let Some(gesture) = self.gestures.on_touch(point, event.pen_state, now) {
+                self.events.push_back(InputEvent::Touch(gesture));
+            }
+        }
+        if let Some(gesture) = self.gestures.on_timeout(now) {
+            self.events.push_back(InputEvent::Touch(gesture));
+        }
+    }
+}
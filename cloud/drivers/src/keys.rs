@@ -1,38 +1,68 @@
 use std::{io, mem::size_of};
 
-use memmap2::{MmapOptions, MmapRaw};
+use crate::{devmem::Region, DevMem, LW_BRIDGE_BASE};
 
-use crate::{DevMem, LW_BRIDGE_BASE};
+pub(crate) const KEY_BASE: u64 = LW_BRIDGE_BASE + 0x00000050;
+const KEY_SPAN: usize = size_of::<u32>() * 4;
 
-const KEY_BASE: u64 = 0x00000050;
+pub(crate) const KEY_DATA_OFFSET: usize = 0x0;
+// latches each key as it is released, until a 1 is written to its bit
+pub(crate) const KEY_EDGE_OFFSET: usize = 0xC;
 
 pub type KeysPressed = [bool; 4];
 
 pub struct Keys {
-    keys: MmapRaw,
-    prev: u8,
+    keys: Region,
 }
 
 impl Keys {
-    pub fn new(DevMem(mem): &DevMem) -> io::Result<Self> {
-        let keys = MmapOptions::new()
-            .offset(LW_BRIDGE_BASE + KEY_BASE)
-            .len(size_of::<u8>())
-            .map_raw(mem)?;
+    pub fn new(mem: &DevMem) -> io::Result<Self> {
+        let keys = mem.map(KEY_BASE, KEY_SPAN)?;
 
-        Ok(Self { keys, prev: 0u8 })
+        Ok(Self { keys })
     }
 
-    pub fn read(&mut self) -> KeysPressed {
-        let cur = unsafe { self.keys.as_ptr().read_volatile() };
-        let delta = !self.prev & cur;
-        self.prev = cur;
-
-        [
-            (delta & (1 << 0)) != 0,
-            (delta & (1 << 1)) != 0,
-            (delta & (1 << 2)) != 0,
-            (delta & (1 << 3)) != 0,
-        ]
+    // which keys are down right now, rather than which have just gone down
+    pub fn held(&self) -> KeysPressed {
+        let cur = self.keys.reg(KEY_DATA_OFFSET).read();
+
+        std::array::from_fn(|i| cur & (1 << i) != 0)
+    }
+
+    // which keys have been let go since this was last called,
+    // even if they were pressed and released too quickly for `held` to see
+    pub fn take_released(&mut self) -> KeysPressed {
+        let edge = self.keys.reg(KEY_EDGE_OFFSET);
+        let released = edge.read();
+        // only the ones that were read, so any let go since aren't lost
+        edge.write(released);
+
+        std::array::from_fn(|i| released & (1 << i) != 0)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::Simulator;
+
+    use super::*;
+
+    #[test]
+    fn test_take_released() -> io::Result<()> {
+        let sim = Simulator::new()?;
+        let mut keys = Keys::new(&DevMem::simulated(sim.clone()))?;
+
+        // a tap between two reads
+        sim.press_key(2);
+        sim.release_key(2);
+        assert_eq!(keys.held(), [false; 4]);
+        assert_eq!(keys.take_released(), [false, false, true, false]);
+        assert_eq!(keys.take_released(), [false; 4]);
+
+        sim.press_key(0);
+        assert_eq!(keys.held(), [true, false, false, false]);
+        assert_eq!(keys.take_released(), [false; 4]);
+
+        Ok(())
     }
 }
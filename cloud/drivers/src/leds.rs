@@ -0,0 +1,51 @@
+use std::{io, mem::size_of};
+
+use crate::{devmem::Region, DevMem, LW_BRIDGE_BASE};
+
+pub(crate) const LED_BASE: u64 = LW_BRIDGE_BASE;
+const LED_SPAN: usize = size_of::<u32>();
+
+pub const LED_COUNT: usize = 10;
+
+// the red leds above the switches, LEDR0 first
+pub struct Leds {
+    leds: Region,
+    // the register can be read back, but this saves going over the bridge
+    on: u32,
+}
+
+impl Leds {
+    pub fn new(mem: &DevMem) -> io::Result<Self> {
+        let leds = mem.map(LED_BASE, LED_SPAN)?;
+
+        let mut res = Self { leds, on: 0 };
+
+        // turn them all off upon opening
+        res.clear();
+
+        Ok(res)
+    }
+
+    pub fn set(&mut self, led: usize, on: bool) {
+        assert!(led < LED_COUNT);
+
+        if on {
+            self.on |= 1 << led;
+        } else {
+            self.on &= !(1 << led);
+        }
+        self.leds.reg(0).write(self.on);
+    }
+
+    pub fn write(&mut self, on: [bool; LED_COUNT]) {
+        self.on = on
+            .iter()
+            .enumerate()
+            .fold(0, |bits, (i, &on)| bits | (on as u32) << i);
+        self.leds.reg(0).write(self.on);
+    }
+
+    pub fn clear(&mut self) {
+        self.write([false; LED_COUNT]);
+    }
+}
@@ -1,19 +1,41 @@
 #![feature(core_intrinsics)]
 
+mod audio;
+mod calibration;
 mod camera;
 mod devmem;
+mod font;
+mod gesture;
 mod hex;
+mod input;
 mod keys;
+mod leds;
+mod ps2;
+mod sim;
+mod switches;
 mod texture;
+mod timer;
 mod touchscreen;
 mod vga;
 
 const LW_BRIDGE_BASE: u64 = 0xFF200000;
 
+pub use audio::{tone, AudioOut, SAMPLE_RATE};
+pub use calibration::{Calibration, CALIBRATION_TARGETS};
 pub use camera::{Camera, CameraGuard};
 pub use devmem::DevMem;
+pub use font::{text_height, Align, TextStyle, GLYPH_SIZE};
+pub use gesture::{Direction, Gesture, Gestures};
 pub use hex::HexDisplay;
+pub use input::{Input, InputEvent};
 pub use keys::{Keys, KeysPressed};
-pub use texture::Texture;
-pub use touchscreen::{TouchArea, TouchEvent, TouchScreen, TOUCHSCREEN_HEIGHT, TOUCHSCREEN_WIDTH};
+pub use leds::{Leds, LED_COUNT};
+pub use ps2::{KeyboardEvent, KeyboardKey, Ps2Keyboard};
+pub use sim::Simulator;
+pub use switches::{Switches, SwitchesOn, SWITCH_COUNT};
+pub use texture::{Rect, Texture};
+pub use timer::IntervalTimer;
+pub use touchscreen::{
+    PenState, TouchArea, TouchEvent, TouchScreen, TOUCHSCREEN_HEIGHT, TOUCHSCREEN_WIDTH,
+};
 pub use vga::VgaDisplay;
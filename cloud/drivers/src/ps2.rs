@@ -0,0 +1,263 @@
+use std::{io, mem::size_of};
+
+use crate::{devmem::Region, DevMem, LW_BRIDGE_BASE};
+
+// not in our Computer_System.qsys, this is where address_map_arm.h puts the PS/2 port
+pub(crate) const PS2_BASE: u64 = LW_BRIDGE_BASE + 0x100;
+const PS2_SPAN: usize = size_of::<u32>() * 2;
+
+// reading the data register pops a byte off the fifo, and this says whether there was one
+pub(crate) const RVALID: u32 = 1 << 15;
+
+// what the port's fifo holds, so one read never takes more than the keyboard could have sent
+const MAX_READ: usize = 256;
+
+// scan code set 2 sends this before the code when a key is let go
+pub(crate) const RELEASE_PREFIX: u8 = 0xF0;
+// and this before the code for keys that were added later, like the arrows
+const EXTENDED_PREFIX: u8 = 0xE0;
+
+// the keys we have a use for, anything else is passed along as its scan code
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum KeyboardKey {
+    // lowercase, since shift is a key of its own
+    Char(char),
+    Enter,
+    Escape,
+    Backspace,
+    Tab,
+    Up,
+    Down,
+    Left,
+    Right,
+    // with the extended prefix in the upper byte, if it had one
+    Other(u16),
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct KeyboardEvent {
+    pub key: KeyboardKey,
+    pub pressed: bool,
+}
+
+const SCAN_CODES: [(u8, KeyboardKey); 52] = [
+    (0x1C, KeyboardKey::Char('a')),
+    (0x32, KeyboardKey::Char('b')),
+    (0x21, KeyboardKey::Char('c')),
+    (0x23, KeyboardKey::Char('d')),
+    (0x24, KeyboardKey::Char('e')),
+    (0x2B, KeyboardKey::Char('f')),
+    (0x34, KeyboardKey::Char('g')),
+    (0x33, KeyboardKey::Char('h')),
+    (0x43, KeyboardKey::Char('i')),
+    (0x3B, KeyboardKey::Char('j')),
+    (0x42, KeyboardKey::Char('k')),
+    (0x4B, KeyboardKey::Char('l')),
+    (0x3A, KeyboardKey::Char('m')),
+    (0x31, KeyboardKey::Char('n')),
+    (0x44, KeyboardKey::Char('o')),
+    (0x4D, KeyboardKey::Char('p')),
+    (0x15, KeyboardKey::Char('q')),
+    (0x2D, KeyboardKey::Char('r')),
+    (0x1B, KeyboardKey::Char('s')),
+    (0x2C, KeyboardKey::Char('t')),
+    (0x3C, KeyboardKey::Char('u')),
+    (0x2A, KeyboardKey::Char('v')),
+    (0x1D, KeyboardKey::Char('w')),
+    (0x22, KeyboardKey::Char('x')),
+    (0x35, KeyboardKey::Char('y')),
+    (0x1A, KeyboardKey::Char('z')),
+    (0x45, KeyboardKey::Char('0')),
+    (0x16, KeyboardKey::Char('1')),
+    (0x1E, KeyboardKey::Char('2')),
+    (0x26, KeyboardKey::Char('3')),
+    (0x25, KeyboardKey::Char('4')),
+    (0x2E, KeyboardKey::Char('5')),
+    (0x36, KeyboardKey::Char('6')),
+    (0x3D, KeyboardKey::Char('7')),
+    (0x3E, KeyboardKey::Char('8')),
+    (0x46, KeyboardKey::Char('9')),
+    (0x29, KeyboardKey::Char(' ')),
+    (0x0E, KeyboardKey::Char('`')),
+    (0x4E, KeyboardKey::Char('-')),
+    (0x55, KeyboardKey::Char('=')),
+    (0x54, KeyboardKey::Char('[')),
+    (0x5B, KeyboardKey::Char(']')),
+    (0x5D, KeyboardKey::Char('\\')),
+    (0x4C, KeyboardKey::Char(';')),
+    (0x52, KeyboardKey::Char('\'')),
+    (0x41, KeyboardKey::Char(',')),
+    (0x49, KeyboardKey::Char('.')),
+    (0x4A, KeyboardKey::Char('/')),
+    (0x5A, KeyboardKey::Enter),
+    (0x76, KeyboardKey::Escape),
+    (0x66, KeyboardKey::Backspace),
+    (0x0D, KeyboardKey::Tab),
+];
+
+const EXTENDED_SCAN_CODES: [(u8, KeyboardKey); 5] = [
+    // on the keypad
+    (0x5A, KeyboardKey::Enter),
+    (0x75, KeyboardKey::Up),
+    (0x72, KeyboardKey::Down),
+    (0x6B, KeyboardKey::Left),
+    (0x74, KeyboardKey::Right),
+];
+
+impl KeyboardKey {
+    fn from_scan_code(code: u8, extended: bool) -> Self {
+        let table: &[_] = if extended {
+            &EXTENDED_SCAN_CODES
+        } else {
+            &SCAN_CODES
+        };
+
+        table.iter().find(|&&(c, _)| c == code).map_or(
+            Self::Other((extended as u16) << 8 | code as u16),
+            |&(_, key)| key,
+        )
+    }
+
+    // the bytes the keyboard sends for this key, without the release prefix
+    pub(crate) fn scan_code(self) -> Vec<u8> {
+        if let Self::Other(code) = self {
+            return match code >> 8 {
+                0 => vec![code as u8],
+                _ => vec![EXTENDED_PREFIX, code as u8],
+            };
+        }
+
+        if let Some(&(code, _)) = SCAN_CODES.iter().find(|&&(_, key)| key == self) {
+            vec![code]
+        } else if let Some(&(code, _)) = EXTENDED_SCAN_CODES.iter().find(|&&(_, key)| key == self) {
+            vec![EXTENDED_PREFIX, code]
+        } else {
+            panic!("{self:?} has no scan code")
+        }
+    }
+}
+
+pub struct Ps2Keyboard {
+    port: Region,
+    decoder: Decoder,
+}
+
+impl Ps2Keyboard {
+    pub fn new(mem: &DevMem) -> io::Result<Self> {
+        let port = mem.map(PS2_BASE, PS2_SPAN)?;
+
+        Ok(Self {
+            port,
+            decoder: Decoder::default(),
+        })
+    }
+
+    // the keys that went up or down since the last read
+    // a code that's only partly arrived is finished off by the next one
+    pub fn read(&mut self) -> Vec<KeyboardEvent> {
+        let mut events = vec![];
+
+        for _ in 0..MAX_READ {
+            let data = self.port.reg(0).read();
+            if data & RVALID == 0 {
+                break;
+            }
+
+            events.extend(self.decoder.push(data as u8));
+        }
+
+        events
+    }
+}
+
+// prefixes seen since the last whole code
+#[derive(Default)]
+struct Decoder {
+    release: bool,
+    extended: bool,
+}
+
+impl Decoder {
+    fn push(&mut self, byte: u8) -> Option<KeyboardEvent> {
+        match byte {
+            RELEASE_PREFIX => self.release = true,
+            EXTENDED_PREFIX => self.extended = true,
+            // the keyboard saying it passed its self test, acknowledging a command, or erroring
+            0xAA | 0xFA | 0xFE | 0xEE | 0x00 | 0xFF => {}
+            code => {
+                let event = KeyboardEvent {
+                    key: KeyboardKey::from_scan_code(code, self.extended),
+                    pressed: !self.release,
+                };
+                *self = Self::default();
+
+                return Some(event);
+            }
+        }
+
+        None
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::Simulator;
+
+    #[test]
+    fn test_decode() {
+        let mut decoder = Decoder::default();
+        let mut decode = |bytes: &[u8]| -> Vec<_> {
+            bytes
+                .iter()
+                .filter_map(|&byte| decoder.push(byte))
+                .map(|event| (event.key, event.pressed))
+                .collect()
+        };
+
+        assert_eq!(
+            decode(&[0xAA, 0x1C, 0xF0, 0x1C]),
+            [
+                (KeyboardKey::Char('a'), true),
+                (KeyboardKey::Char('a'), false)
+            ]
+        );
+        assert_eq!(
+            decode(&[0xE0, 0x75, 0xE0, 0xF0, 0x75]),
+            [(KeyboardKey::Up, true), (KeyboardKey::Up, false)]
+        );
+        assert_eq!(decode(&[0x12]), [(KeyboardKey::Other(0x12), true)]);
+
+        for key in [
+            KeyboardKey::Enter,
+            KeyboardKey::Left,
+            KeyboardKey::Other(0x112),
+        ] {
+            assert_eq!(decode(&key.scan_code()), [(key, true)]);
+        }
+    }
+
+    #[test]
+    fn test_read_limit() -> io::Result<()> {
+        let sim = Simulator::new()?;
+        let mut keyboard = Ps2Keyboard::new(&DevMem::simulated(sim.clone()))?;
+
+        // a whole fifo and then some, with a release cut in two by the limit
+        for _ in 0..MAX_READ - 1 {
+            sim.keyboard(KeyboardKey::Char('a'), true);
+        }
+        sim.keyboard(KeyboardKey::Char('a'), false);
+
+        assert_eq!(keyboard.read().len(), MAX_READ - 1);
+        assert_eq!(
+            keyboard.read(),
+            [KeyboardEvent {
+                key: KeyboardKey::Char('a'),
+                pressed: false,
+            }]
+        );
+        assert_eq!(keyboard.read(), []);
+
+        Ok(())
+    }
+}
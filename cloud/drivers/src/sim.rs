@@ -0,0 +1,461 @@
+use std::{
+    collections::VecDeque,
+    fs::{File, OpenOptions},
+    io,
+    os::fd::FromRawFd,
+    path::Path,
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+use memmap2::{MmapOptions, MmapRaw};
+
+use crate::{
+    audio::{
+        AUDIO_BASE, CLEAR_WRITE, CONTROL_OFFSET as AUDIO_CONTROL_OFFSET, FIFOSPACE_OFFSET,
+        FIFO_SIZE, LEFT_DATA_OFFSET, RIGHT_DATA_OFFSET, SAMPLE_RATE,
+    },
+    camera::{BUFFER_BASE as CAMERA_BUF_BASE, CAMERA_ENABLE, CONTROL_OFFSET, VIDEO_IN_BASE},
+    hex::{HEX_BASE, HEX_HIGH_OFFSET},
+    keys::{KEY_BASE, KEY_DATA_OFFSET, KEY_EDGE_OFFSET},
+    leds::{LED_BASE, LED_COUNT},
+    ps2::{KeyboardKey, PS2_BASE, RELEASE_PREFIX, RVALID},
+    switches::{SWITCH_BASE, SWITCH_COUNT},
+    timer::{
+        CONTROL_OFFSET as TIMER_CONTROL_OFFSET, PERIOD_HIGH_OFFSET, PERIOD_LOW_OFFSET, START,
+        STATUS_OFFSET as TIMER_STATUS_OFFSET, STOP, TIMEOUT, TIMER_BASE, TIMER_FREQUENCY,
+    },
+    touchscreen::{PenState, RRDY, TOUCHSCREEN_BASE},
+    vga::{
+        BACK_BUFFER_OFFSET, BUFFER_OFFSET, CHAR_BUF_BASE, CHAR_BUF_HEIGHT, CHAR_BUF_SPAN,
+        CHAR_BUF_WIDTH, DISPLAY_ENABLE, PIXEL_BUF1_BASE, PIXEL_BUF2_BASE, PIXEL_BUF_CTRL_BASE,
+        PIXEL_BUF_HEIGHT, PIXEL_BUF_SPAN, PIXEL_BUF_WIDTH, RESOLUTION_OFFSET, STATUS_FLAG,
+        STATUS_OFFSET,
+    },
+    LW_BRIDGE_BASE,
+};
+
+// only the start of the bridge has anything on it
+const LW_BRIDGE_SPAN: u64 = 0x00010000;
+
+// physical spans backed by the simulator, rounded up to whole pages
+const SPANS: [(u64, u64); 4] = [
+    (LW_BRIDGE_BASE, LW_BRIDGE_SPAN),
+    (PIXEL_BUF2_BASE, PIXEL_BUF_SPAN + 1),
+    (PIXEL_BUF1_BASE, PIXEL_BUF_SPAN + 1),
+    (CHAR_BUF_BASE, CHAR_BUF_SPAN + 1),
+];
+
+// registers with behaviour beyond plain memory
+const VGA_BUFFER: u64 = PIXEL_BUF_CTRL_BASE + BUFFER_OFFSET as u64;
+const VGA_BACK_BUFFER: u64 = PIXEL_BUF_CTRL_BASE + BACK_BUFFER_OFFSET as u64;
+const VGA_RESOLUTION: u64 = PIXEL_BUF_CTRL_BASE + RESOLUTION_OFFSET as u64;
+const VGA_STATUS: u64 = PIXEL_BUF_CTRL_BASE + STATUS_OFFSET as u64;
+const KEY_DATA: u64 = KEY_BASE + KEY_DATA_OFFSET as u64;
+const KEY_EDGE: u64 = KEY_BASE + KEY_EDGE_OFFSET as u64;
+const TIMER_STATUS: u64 = TIMER_BASE + TIMER_STATUS_OFFSET as u64;
+const TIMER_CONTROL: u64 = TIMER_BASE + TIMER_CONTROL_OFFSET as u64;
+const AUDIO_CONTROL: u64 = AUDIO_BASE + AUDIO_CONTROL_OFFSET as u64;
+const AUDIO_FIFOSPACE: u64 = AUDIO_BASE + FIFOSPACE_OFFSET as u64;
+const AUDIO_LEFT: u64 = AUDIO_BASE + LEFT_DATA_OFFSET as u64;
+const AUDIO_RIGHT: u64 = AUDIO_BASE + RIGHT_DATA_OFFSET as u64;
+
+// the display only swaps buffers between frames, at 60 Hz
+const FRAME_TIME: Duration = Duration::from_micros(16_667);
+
+/// Stands in for the board's physical memory, so that peripherals opened with
+/// [`DevMem::simulated`](crate::DevMem::simulated) can run on any Linux machine.
+pub struct Simulator {
+    file: File,
+    // all of the simulated memory, for looking at what the peripherals did
+    mem: MmapRaw,
+    state: Mutex<State>,
+}
+
+#[derive(Default)]
+struct State {
+    front_buffer: u32,
+    back_buffer: u32,
+    vga_control: u32,
+    // when the requested swap happens
+    swap_at: Option<Instant>,
+    keys_down: u32,
+    keys_released: u32,
+    touch_fifo: VecDeque<u8>,
+    switches: u32,
+    keyboard_fifo: VecDeque<u8>,
+    timer: Option<Timer>,
+    audio: Audio,
+}
+
+// a running interval timer
+struct Timer {
+    started: Instant,
+    period: Duration,
+    // how many times it has gone off that have been acknowledged
+    seen: u32,
+}
+
+impl Timer {
+    fn timeouts(&self) -> u32 {
+        (self.started.elapsed().as_nanos() / self.period.as_nanos()) as u32
+    }
+}
+
+// the line out, which takes samples off its fifo at the sample rate
+#[derive(Default)]
+struct Audio {
+    queued: u32,
+    // when the last sample taken off the fifo was played
+    drained_at: Option<Instant>,
+    // everything that's gone out, for looking at later
+    played: Vec<i32>,
+}
+
+impl Audio {
+    fn drain(&mut self) {
+        let now = Instant::now();
+        let at = *self.drained_at.get_or_insert(now);
+        let drained = ((now - at).as_secs_f64() * SAMPLE_RATE as f64) as u32;
+
+        self.queued = self.queued.saturating_sub(drained);
+        self.drained_at = Some(if self.queued == 0 {
+            now
+        } else {
+            at + Duration::from_secs_f64(drained as f64 / SAMPLE_RATE as f64)
+        });
+    }
+}
+
+impl State {
+    fn finish_swap(&mut self) {
+        if self.swap_at.is_some_and(|at| at <= Instant::now()) {
+            self.swap_at = None;
+            std::mem::swap(&mut self.front_buffer, &mut self.back_buffer);
+        }
+    }
+}
+
+impl Simulator {
+    // memory is anonymous, and goes away with the simulator
+    pub fn new() -> io::Result<Arc<Self>> {
+        let fd = unsafe { libc::memfd_create(c"devmem".as_ptr(), libc::MFD_CLOEXEC) };
+        if fd < 0 {
+            return Err(io::Error::last_os_error());
+        }
+
+        Self::from_file(unsafe { File::from_raw_fd(fd) })
+    }
+
+    // memory is kept in a file, so that other processes can look at it
+    pub fn with_file(path: impl AsRef<Path>) -> io::Result<Arc<Self>> {
+        Self::from_file(
+            OpenOptions::new()
+                .read(true)
+                .write(true)
+                .create(true)
+                .truncate(true)
+                .open(path)?,
+        )
+    }
+
+    fn from_file(file: File) -> io::Result<Arc<Self>> {
+        let len: u64 = SPANS.iter().map(|(_, span)| span).sum();
+        file.set_len(len)?;
+        let mem = MmapOptions::new().len(len as usize).map_raw(&file)?;
+
+        let sim = Self {
+            file,
+            mem,
+            state: Mutex::new(State {
+                front_buffer: PIXEL_BUF1_BASE as u32,
+                back_buffer: PIXEL_BUF2_BASE as u32,
+                ..Default::default()
+            }),
+        };
+        // the camera writes straight into the first pixel buffer
+        unsafe {
+            (sim.ptr(VIDEO_IN_BASE) as *mut u32).write_volatile(CAMERA_BUF_BASE as u32);
+        }
+
+        Ok(Arc::new(sim))
+    }
+
+    // where a physical address is in the simulated memory
+    fn offset(base: u64, len: usize) -> io::Result<u64> {
+        let mut offset = 0;
+        for (span_base, span) in SPANS {
+            if (span_base..span_base + span).contains(&base)
+                && base + len as u64 <= span_base + span
+            {
+                return Ok(offset + base - span_base);
+            }
+            offset += span;
+        }
+
+        Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!("{base:#x} is not simulated"),
+        ))
+    }
+
+    fn ptr(&self, addr: u64) -> *mut u8 {
+        let offset = Self::offset(addr, 1).expect("address should be simulated");
+        unsafe { self.mem.as_mut_ptr().add(offset as usize) }
+    }
+
+    pub(crate) fn map(&self, base: u64, len: usize) -> io::Result<MmapRaw> {
+        MmapOptions::new()
+            .offset(Self::offset(base, len)?)
+            .len(len)
+            .map_raw(&self.file)
+    }
+
+    // what reading a register gives, if it isn't just memory
+    pub(crate) fn read(&self, addr: u64) -> Option<u32> {
+        let mut state = self.state.lock().unwrap();
+
+        match addr {
+            VGA_BUFFER => Some(state.front_buffer),
+            VGA_BACK_BUFFER => Some(state.back_buffer),
+            VGA_RESOLUTION => Some((PIXEL_BUF_HEIGHT << 16 | PIXEL_BUF_WIDTH) as u32),
+            VGA_STATUS => {
+                state.finish_swap();
+                let busy = if state.swap_at.is_some() {
+                    STATUS_FLAG
+                } else {
+                    0
+                };
+                Some(state.vga_control | busy)
+            }
+            KEY_DATA => Some(state.keys_down),
+            KEY_EDGE => Some(state.keys_released),
+            TOUCHSCREEN_BASE => {
+                // the number of bytes left is in the upper half
+                let data = state.touch_fifo.pop_front().map_or(0, |byte| {
+                    (state.touch_fifo.len() as u32) << 16 | RRDY | byte as u32
+                });
+                Some(data)
+            }
+            SWITCH_BASE => Some(state.switches),
+            TIMER_STATUS => {
+                let timeout = state
+                    .timer
+                    .as_ref()
+                    .is_some_and(|timer| timer.timeouts() > timer.seen);
+                // whether it's running is the next bit up
+                Some(if timeout { TIMEOUT } else { 0 } | (state.timer.is_some() as u32) << 1)
+            }
+            PS2_BASE => {
+                let data = state.keyboard_fifo.pop_front().map_or(0, |byte| {
+                    (state.keyboard_fifo.len() as u32) << 16 | RVALID | byte as u32
+                });
+                Some(data)
+            }
+            AUDIO_FIFOSPACE => {
+                state.audio.drain();
+                // room in the left and right write fifos, there's nothing to read
+                let room = FIFO_SIZE - state.audio.queued;
+                Some(room << 24 | room << 16)
+            }
+            _ => None,
+        }
+    }
+
+    // whether writing to a register was handled, instead of going to memory
+    pub(crate) fn write(&self, addr: u64, value: u32) -> bool {
+        let mut state = self.state.lock().unwrap();
+
+        match addr {
+            // any write asks for the buffers to be swapped
+            VGA_BUFFER => {
+                state.swap_at.get_or_insert(Instant::now() + FRAME_TIME);
+            }
+            VGA_BACK_BUFFER => state.back_buffer = value,
+            VGA_STATUS => state.vga_control = value & DISPLAY_ENABLE,
+            KEY_EDGE => state.keys_released &= !value,
+            TIMER_STATUS => {
+                if let Some(timer) = &mut state.timer {
+                    timer.seen = timer.timeouts();
+                }
+            }
+            TIMER_CONTROL => {
+                if value & STOP != 0 {
+                    state.timer = None;
+                }
+                if value & START != 0 {
+                    let half = |offset: usize| {
+                        u32::from_be_bytes(self.read_memory(TIMER_BASE + offset as u64)) & 0xFFFF
+                    };
+                    let counts =
+                        (half(PERIOD_HIGH_OFFSET) << 16 | half(PERIOD_LOW_OFFSET)) as u64 + 1;
+
+                    state.timer = Some(Timer {
+                        started: Instant::now(),
+                        period: Duration::from_nanos(counts * 1_000_000_000 / TIMER_FREQUENCY),
+                        seen: 0,
+                    });
+                }
+            }
+            // both channels get the same samples, so only the left is kept
+            AUDIO_LEFT => {
+                state.audio.drain();
+                if state.audio.queued < FIFO_SIZE {
+                    state.audio.queued += 1;
+                    state.audio.played.push(value as i32);
+                }
+            }
+            AUDIO_CONTROL => {
+                if value & CLEAR_WRITE != 0 {
+                    state.audio.queued = 0;
+                }
+                // the rest of it is kept as written
+                return false;
+            }
+            // read-only, or nothing on the other end is listening
+            KEY_DATA | VGA_RESOLUTION | TOUCHSCREEN_BASE | SWITCH_BASE | PS2_BASE
+            | AUDIO_FIFOSPACE | AUDIO_RIGHT => {}
+            _ => return false,
+        }
+
+        true
+    }
+
+    pub fn press_key(&self, key: usize) {
+        assert!(key < 4);
+
+        self.state.lock().unwrap().keys_down |= 1 << key;
+    }
+
+    pub fn release_key(&self, key: usize) {
+        assert!(key < 4);
+
+        let mut state = self.state.lock().unwrap();
+        if state.keys_down & 1 << key != 0 {
+            state.keys_down &= !(1 << key);
+            state.keys_released |= 1 << key;
+        }
+    }
+
+    // queues up a packet as the touchscreen controller would send it
+    pub fn touch(&self, x: usize, y: usize, pen_state: PenState) {
+        let pen = match pen_state {
+            PenState::Down => 0x81,
+            PenState::Up => 0x80,
+        };
+
+        self.state.lock().unwrap().touch_fifo.extend([
+            pen,
+            (x & 0x7F) as u8,
+            (x >> 7 & 0x1F) as u8,
+            (y & 0x7F) as u8,
+            (y >> 7 & 0x1F) as u8,
+        ]);
+    }
+
+    pub fn set_switch(&self, switch: usize, on: bool) {
+        assert!(switch < SWITCH_COUNT);
+
+        let mut state = self.state.lock().unwrap();
+        if on {
+            state.switches |= 1 << switch;
+        } else {
+            state.switches &= !(1 << switch);
+        }
+    }
+
+    // queues up the scan codes for a key going down or up
+    pub fn keyboard(&self, key: KeyboardKey, pressed: bool) {
+        let mut code = key.scan_code();
+        if !pressed {
+            // the release prefix goes after the extended one
+            code.insert(code.len() - 1, RELEASE_PREFIX);
+        }
+
+        self.state.lock().unwrap().keyboard_fifo.extend(code);
+    }
+
+    // which of the red leds are lit, LEDR0 first
+    pub fn leds(&self) -> [bool; LED_COUNT] {
+        let on = u32::from_be_bytes(self.read_memory(LED_BASE));
+
+        std::array::from_fn(|i| on & (1 << i) != 0)
+    }
+
+    // the samples sent to the line out since this was last called
+    pub fn take_audio(&self) -> Vec<i32> {
+        std::mem::take(&mut self.state.lock().unwrap().audio.played)
+    }
+
+    pub fn display_enabled(&self) -> bool {
+        self.state.lock().unwrap().vga_control & DISPLAY_ENABLE != 0
+    }
+
+    // the pixels being shown, row by row
+    pub fn front_buffer(&self) -> Vec<u16> {
+        let front = {
+            let mut state = self.state.lock().unwrap();
+            state.finish_swap();
+            state.front_buffer
+        };
+        let buf_ptr = self.ptr(front as u64);
+
+        let mut pixels = Vec::with_capacity(PIXEL_BUF_WIDTH * PIXEL_BUF_HEIGHT);
+        for y in 0..PIXEL_BUF_HEIGHT {
+            for x in 0..PIXEL_BUF_WIDTH {
+                pixels
+                    .push(unsafe { (buf_ptr.add(x << 1 | y << 10) as *const u16).read_volatile() });
+            }
+        }
+
+        pixels
+    }
+
+    // the character buffer, one string per row
+    pub fn text(&self) -> Vec<String> {
+        let char_ptr = self.ptr(CHAR_BUF_BASE);
+
+        (0..CHAR_BUF_HEIGHT)
+            .map(|y| {
+                (0..CHAR_BUF_WIDTH)
+                    .map(
+                        |x| match unsafe { char_ptr.add(x | y << 7).read_volatile() } {
+                            c @ 0x20..=0x7E => c as char,
+                            _ => ' ',
+                        },
+                    )
+                    .collect()
+            })
+            .collect()
+    }
+
+    // segments lit on each of the six digits, from the left
+    pub fn hex(&self) -> [u8; 6] {
+        let [_, _, hex5, hex4] = self.read_memory(HEX_BASE + HEX_HIGH_OFFSET as u64);
+        let [hex3, hex2, hex1, hex0] = self.read_memory(HEX_BASE);
+
+        [hex5, hex4, hex3, hex2, hex1, hex0]
+    }
+
+    pub fn camera_enabled(&self) -> bool {
+        u32::from_be_bytes(self.read_memory(VIDEO_IN_BASE + CONTROL_OFFSET as u64)) & CAMERA_ENABLE
+            != 0
+    }
+
+    // puts a frame where the camera would have, row by row
+    pub fn set_camera_frame(&self, pixels: &[u16]) {
+        assert_eq!(pixels.len(), PIXEL_BUF_WIDTH * PIXEL_BUF_HEIGHT);
+
+        let buf_ptr = self.ptr(CAMERA_BUF_BASE);
+        for (i, &pixel) in pixels.iter().enumerate() {
+            let (y, x) = (i / PIXEL_BUF_WIDTH, i % PIXEL_BUF_WIDTH);
+            unsafe {
+                (buf_ptr.add(x << 1 | y << 10) as *mut u16).write_volatile(pixel);
+            }
+        }
+    }
+
+    fn read_memory(&self, addr: u64) -> [u8; 4] {
+        unsafe { (self.ptr(addr) as *const u32).read_volatile() }.to_be_bytes()
+    }
+}
@@ -0,0 +1,29 @@
+use std::{io, mem::size_of};
+
+use crate::{devmem::Region, DevMem, LW_BRIDGE_BASE};
+
+pub(crate) const SWITCH_BASE: u64 = LW_BRIDGE_BASE + 0x40;
+const SWITCH_SPAN: usize = size_of::<u32>();
+
+pub const SWITCH_COUNT: usize = 10;
+
+// which switches are on, SW0 first
+pub type SwitchesOn = [bool; SWITCH_COUNT];
+
+pub struct Switches {
+    switches: Region,
+}
+
+impl Switches {
+    pub fn new(mem: &DevMem) -> io::Result<Self> {
+        let switches = mem.map(SWITCH_BASE, SWITCH_SPAN)?;
+
+        Ok(Self { switches })
+    }
+
+    pub fn read(&self) -> SwitchesOn {
+        let data = self.switches.reg(0).read();
+
+        std::array::from_fn(|i| data & (1 << i) != 0)
+    }
+}
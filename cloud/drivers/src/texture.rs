@@ -1,9 +1,18 @@
-// TODO: add transparent stuff later if we need it
-
 pub struct Texture {
     width: usize,
     height: usize,
     data: Vec<u16>,
+    // how opaque each pixel is out of 255, unless they all are
+    alpha: Option<Vec<u8>>,
+}
+
+// part of a texture, in its own pixels
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Rect {
+    pub x: usize,
+    pub y: usize,
+    pub width: usize,
+    pub height: usize,
 }
 
 impl Texture {
@@ -14,7 +23,29 @@ impl Texture {
             height,
             width,
             data,
+            alpha: None,
+        }
+    }
+
+    pub fn with_alpha(width: usize, height: usize, data: Vec<u16>, alpha: Vec<u8>) -> Self {
+        assert!(alpha.len() == data.len());
+
+        let mut texture = Self::new(width, height, data);
+        // no need to blend if nothing shows through
+        if alpha.iter().any(|&a| a != u8::MAX) {
+            texture.alpha = Some(alpha);
         }
+        texture
+    }
+
+    // pixels of the key colour are left out when drawn
+    pub fn with_color_key(width: usize, height: usize, data: Vec<u16>, key: u16) -> Self {
+        let alpha = data
+            .iter()
+            .map(|&color| if color == key { 0 } else { u8::MAX })
+            .collect();
+
+        Self::with_alpha(width, height, data, alpha)
     }
 
     #[inline]
@@ -31,4 +62,47 @@ impl Texture {
     pub fn data(&self) -> &[u16] {
         &self.data
     }
+
+    #[inline]
+    pub fn alpha(&self) -> Option<&[u8]> {
+        self.alpha.as_deref()
+    }
+
+    // the whole texture
+    pub fn rect(&self) -> Rect {
+        Rect {
+            x: 0,
+            y: 0,
+            width: self.width,
+            height: self.height,
+        }
+    }
+}
+
+// mixes two RGB565 colours, channel by channel
+pub(crate) fn blend(src: u16, dst: u16, alpha: u8) -> u16 {
+    let alpha = alpha as u32;
+    let mix = |shift: u32, mask: u32| {
+        let src = (src as u32 >> shift) & mask;
+        let dst = (dst as u32 >> shift) & mask;
+        ((src * alpha + dst * (255 - alpha) + 127) / 255) << shift
+    };
+
+    (mix(11, 0x1F) | mix(5, 0x3F) | mix(0, 0x1F)) as u16
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_blend() {
+        const RED: u16 = 0xF800;
+        const BLUE: u16 = 0x001F;
+
+        assert_eq!(blend(RED, BLUE, 255), RED);
+        assert_eq!(blend(RED, BLUE, 0), BLUE);
+        assert_eq!(blend(RED, BLUE, 128), 0x800F);
+        assert_eq!(blend(0xFFFF, 0x0000, 128), 0x8410);
+    }
 }
@@ -0,0 +1,141 @@
+use std::{io, mem::size_of, time::Duration};
+
+use crate::{
+    devmem::{Region, Register},
+    DevMem, LW_BRIDGE_BASE,
+};
+
+pub(crate) const TIMER_BASE: u64 = LW_BRIDGE_BASE + 0x2000;
+const TIMER_SPAN: usize = size_of::<u32>() * 6;
+
+// the timer counts down at the bridge's clock rate
+pub(crate) const TIMER_FREQUENCY: u64 = 100_000_000;
+
+pub(crate) const STATUS_OFFSET: usize = 0x0;
+pub(crate) const CONTROL_OFFSET: usize = 0x4;
+pub(crate) const PERIOD_LOW_OFFSET: usize = 0x8;
+pub(crate) const PERIOD_HIGH_OFFSET: usize = 0xC;
+
+// set each time the count reaches zero, until the status is written to
+pub(crate) const TIMEOUT: u32 = 1 << 0;
+// start again from the period after reaching zero, instead of stopping
+pub(crate) const CONTINUOUS: u32 = 1 << 1;
+pub(crate) const START: u32 = 1 << 2;
+pub(crate) const STOP: u32 = 1 << 3;
+
+// nothing tells us the timer ran out, so it's checked this often
+// tokio only sleeps to the millisecond, so a tick can still come up to that late
+const POLL_RATE: Duration = Duration::from_micros(500);
+
+struct TimerRegs<'a>(&'a Region);
+
+impl TimerRegs<'_> {
+    #[inline]
+    fn status(&self) -> Register<'_> {
+        self.0.reg(STATUS_OFFSET)
+    }
+
+    #[inline]
+    fn control(&self) -> Register<'_> {
+        self.0.reg(CONTROL_OFFSET)
+    }
+
+    // the period is split over two registers, 16 bits each
+    fn set_period(&self, counts: u32) {
+        self.0.reg(PERIOD_LOW_OFFSET).write(counts & 0xFFFF);
+        self.0.reg(PERIOD_HIGH_OFFSET).write(counts >> 16);
+    }
+}
+
+// the board's Interval_Timer, which keeps time better than sleeping does
+pub struct IntervalTimer {
+    timer: Region,
+}
+
+impl IntervalTimer {
+    pub fn new(mem: &DevMem) -> io::Result<Self> {
+        let timer = mem.map(TIMER_BASE, TIMER_SPAN)?;
+
+        let res = Self { timer };
+
+        // make sure it's not still running from before
+        res.stop();
+
+        Ok(res)
+    }
+
+    // goes off every `period` until stopped, starting a period from now
+    pub fn start(&mut self, period: Duration) {
+        let counts = period.as_nanos() * TIMER_FREQUENCY as u128 / 1_000_000_000;
+        assert!(
+            (1..=u32::MAX as u128).contains(&counts),
+            "timer can't count {period:?}"
+        );
+
+        let regs = TimerRegs(&self.timer);
+        regs.control().write(STOP);
+        // it counts down to zero inclusive
+        regs.set_period(counts as u32 - 1);
+        regs.status().write(0);
+        regs.control().write(CONTINUOUS | START);
+    }
+
+    pub fn stop(&self) {
+        let regs = TimerRegs(&self.timer);
+        regs.control().write(STOP);
+        regs.status().write(0);
+    }
+
+    // waits until the timer next goes off
+    // a period that already went by returns straight away, but any before it are lost
+    // each tick can be a little late, but since the timer keeps counting the lateness
+    // doesn't add up from one tick to the next
+    pub async fn tick(&mut self) {
+        let regs = TimerRegs(&self.timer);
+
+        while regs.status().read() & TIMEOUT == 0 {
+            tokio::time::sleep(POLL_RATE).await;
+        }
+        regs.status().write(0);
+    }
+}
+
+impl Drop for IntervalTimer {
+    fn drop(&mut self) {
+        self.stop();
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use tokio::time::Instant;
+
+    use super::*;
+    use crate::Simulator;
+
+    #[tokio::test]
+    async fn test_tick() -> io::Result<()> {
+        const PERIOD: Duration = Duration::from_millis(20);
+
+        let sim = Simulator::new()?;
+        let mut timer = IntervalTimer::new(&DevMem::simulated(sim))?;
+
+        let start = Instant::now();
+        timer.start(PERIOD);
+        for _ in 0..5 {
+            timer.tick().await;
+        }
+        // late ticks don't push back the ones after them
+        let elapsed = start.elapsed();
+        assert!(elapsed >= 5 * PERIOD, "{elapsed:?}");
+        assert!(elapsed < 6 * PERIOD, "{elapsed:?}");
+
+        // one that went by while we weren't waiting is still there
+        tokio::time::sleep(2 * PERIOD).await;
+        let start = Instant::now();
+        timer.tick().await;
+        assert!(start.elapsed() < PERIOD / 2);
+
+        Ok(())
+    }
+}
@@ -1,28 +1,27 @@
 use std::{intrinsics::size_of, io};
 
-use memmap2::{MmapOptions, MmapRaw};
-use voladdress::{Safe, VolAddress};
+use common_types::{IMAGE_HEIGHT, IMAGE_WIDTH};
 
-use crate::{DevMem, LW_BRIDGE_BASE};
+use crate::{
+    devmem::{Region, Register},
+    Calibration, DevMem, Gesture, Gestures, LW_BRIDGE_BASE,
+};
 
-const TOUCHSCREEN_BASE: u64 = LW_BRIDGE_BASE + 0x1020;
+pub(crate) const TOUCHSCREEN_BASE: u64 = LW_BRIDGE_BASE + 0x1020;
 const TOUCHSCREEN_SPAN: usize = size_of::<u16>() * 3;
 
 pub struct TouchScreen {
-    data: MmapRaw,
+    data: Region,
+    calibration: Calibration,
 }
 
-struct TouchRegs(usize);
+struct TouchRegs<'a>(&'a Region);
 
-impl TouchRegs {
+impl TouchRegs<'_> {
+    // reading pops a byte off the receive fifo
     #[inline]
-    unsafe fn new(base: *const u8) -> Self {
-        Self(base as usize)
-    }
-
-    #[inline]
-    fn data(&self) -> VolAddress<u32, Safe, ()> {
-        unsafe { VolAddress::new(self.0) }
+    fn data(&self) -> Register<'_> {
+        self.0.reg(0)
     }
 
     // #[inline]
@@ -42,7 +41,7 @@ impl TouchRegs {
     // }
 }
 
-const RRDY: u32 = 1 << 15;
+pub(crate) const RRDY: u32 = 1 << 15;
 
 #[derive(Debug)]
 pub struct TouchEvent {
@@ -58,51 +57,45 @@ pub enum PenState {
 }
 
 impl TouchScreen {
-    pub fn new(DevMem(mem): &DevMem) -> io::Result<Self> {
-        let data = MmapOptions::new()
-            .offset(TOUCHSCREEN_BASE)
-            .len(TOUCHSCREEN_SPAN)
-            .map_raw(mem)?;
+    pub fn new(mem: &DevMem) -> io::Result<Self> {
+        let data = mem.map(TOUCHSCREEN_BASE, TOUCHSCREEN_SPAN)?;
+
+        Ok(Self {
+            data,
+            calibration: Calibration::default(),
+        })
+    }
+
+    pub fn set_calibration(&mut self, calibration: Calibration) {
+        self.calibration = calibration;
+    }
 
-        Ok(Self { data })
+    // where on the display a touch was
+    pub fn to_vga(&self, event: &TouchEvent) -> (usize, usize) {
+        self.calibration.to_vga(event.x, event.y)
     }
 
-    // instead of checking raw touches, provide a way to tell when a touch falls within a rectangle
+    // instead of checking raw touches, provide a way to tell when a tap falls within a rectangle
     // linear search because we only have a few
     pub async fn wait_touch(&self, areas: &[TouchArea]) -> usize {
         // flush to start with, in case there were other events
         self.flush().await;
 
+        let mut gestures = Gestures::new();
         loop {
-            // wait for pen down followed by pen up
-            // both have to be inside an area
-            let (x_down, y_down) = loop {
-                let TouchEvent { x, y, pen_state } = self.read().await;
-                if pen_state == PenState::Down {
-                    println!("Press at ({x}, {y})");
-                    break (x, y);
-                }
+            let Gesture::Tap((x, y)) = gestures.next(self).await else {
+                continue;
             };
+            println!("Tap at ({x}, {y})");
 
-            let (x_up, y_up) = loop {
-                let TouchEvent { x, y, pen_state } = self.read().await;
-                if pen_state == PenState::Up {
-                    println!("Release at ({x}, {y})");
-                    break (x, y);
-                }
-            };
-
-            if let Some(i) = areas
-                .iter()
-                .position(|a| a.contains(x_down, y_down) && a.contains(x_up, y_up))
-            {
+            if let Some(i) = areas.iter().position(|a| a.contains((x, y))) {
                 return i;
             }
         }
     }
 
     pub async fn flush(&self) {
-        let regs = unsafe { TouchRegs::new(self.data.as_ptr()) };
+        let regs = TouchRegs(&self.data);
 
         // just read until not ready
         while regs.data().read() & RRDY != 0 {
@@ -111,21 +104,53 @@ impl TouchScreen {
     }
 
     pub async fn read(&self) -> TouchEvent {
-        let [pen, x_lo, x_hi, y_lo, y_hi] = self.read_event_bytes().await;
+        let mut packet = Packet::default();
 
-        // this converts the coordinates to 0-1023
-        let x = (x_hi as usize) << 7 | (x_lo as usize);
-        let y = (y_hi as usize) << 7 | (y_lo as usize);
-        let pen_state = if pen & 1 == 1 {
-            PenState::Down
-        } else {
-            PenState::Up
-        };
+        loop {
+            if let Some(event) = packet.push(self.read_byte().await) {
+                return event;
+            }
+        }
+    }
+
+    // the next event if it has all arrived, keeping any of it that has in `packet` for next time
+    pub(crate) fn try_read(&self, packet: &mut Packet) -> Option<TouchEvent> {
+        while let Some(byte) = self.try_read_byte() {
+            if let Some(event) = packet.push(byte) {
+                return Some(event);
+            }
+        }
+
+        None
+    }
+
+    async fn read_byte(&self) -> u8 {
+        // poll status
+        loop {
+            if let Some(byte) = self.try_read_byte() {
+                return byte;
+            }
+            // hope this works fine
+            tokio::task::yield_now().await;
+        }
+    }
+
+    fn try_read_byte(&self) -> Option<u8> {
+        let data = TouchRegs(&self.data).data().read();
 
-        TouchEvent { x, y, pen_state }
+        (data & RRDY != 0).then_some((data & 0xFF) as u8)
     }
+}
 
-    async fn read_event_bytes(&self) -> [u8; 5] {
+// the bytes of an event read so far
+#[derive(Default)]
+pub(crate) struct Packet {
+    data: [u8; 5],
+    len: usize,
+}
+
+impl Packet {
+    fn push(&mut self, byte: u8) -> Option<TouchEvent> {
         // bitmasks for checking whether byte is valid
         // we also expect the lower 2 bits to be 0's for x and y
         const MASKS: [(u8, u8); 5] = [
@@ -135,41 +160,33 @@ impl TouchScreen {
             (0b0000_0000, 0b1000_0000),
             (0b0000_0000, 0b1110_0000),
         ];
-        let mut data = [0u8; 5];
 
         // keep reading until the data matches the format
-        'outer: loop {
-            for (data, (magic, mask)) in data.iter_mut().zip(MASKS) {
-                let byte = self.read_byte().await;
-
-                if (byte ^ magic) & mask != 0 {
-                    continue 'outer;
-                }
-
-                *data = byte;
-            }
-
-            break;
+        let (magic, mask) = MASKS[self.len];
+        if (byte ^ magic) & mask != 0 {
+            self.len = 0;
+            return None;
         }
 
-        data
-    }
+        self.data[self.len] = byte;
+        self.len += 1;
+        if self.len < self.data.len() {
+            return None;
+        }
+        self.len = 0;
 
-    async fn read_byte(&self) -> u8 {
-        let regs = unsafe { TouchRegs::new(self.data.as_ptr()) };
+        let [pen, x_lo, x_hi, y_lo, y_hi] = self.data;
 
-        // poll status
-        let mut data;
-        loop {
-            data = regs.data().read();
-            if data & RRDY != 0 {
-                break;
-            }
-            // hope this works fine
-            tokio::task::yield_now().await;
-        }
+        // this converts the coordinates to 0-1023
+        let x = (x_hi as usize) << 7 | (x_lo as usize);
+        let y = (y_hi as usize) << 7 | (y_lo as usize);
+        let pen_state = if pen & 1 == 1 {
+            PenState::Down
+        } else {
+            PenState::Up
+        };
 
-        (data & 0xFF) as u8
+        Some(TouchEvent { x, y, pen_state })
     }
 }
 
@@ -185,18 +202,17 @@ pub const TOUCHSCREEN_WIDTH: usize = 4096;
 pub const TOUCHSCREEN_HEIGHT: usize = 4096;
 
 impl TouchArea {
-    // since we don't need anything more complicated, this takes vga coordinates and converts them to touchscreen coordinates
+    // in vga coordinates, which touches are converted to with the calibration
     pub const fn new((x1, y1): (usize, usize), (x2, y2): (usize, usize)) -> Self {
         assert!(x1 <= x2);
-        assert!(x2 < TOUCHSCREEN_WIDTH);
+        assert!(x2 < IMAGE_WIDTH);
         assert!(y1 <= y2);
-        assert!(y2 < TOUCHSCREEN_HEIGHT);
+        assert!(y2 < IMAGE_HEIGHT);
 
         Self { x1, x2, y1, y2 }
     }
 
-    // internal
-    fn contains(&self, x: usize, y: usize) -> bool {
+    pub fn contains(&self, (x, y): (usize, usize)) -> bool {
         let Self { x1, x2, y1, y2 } = *self;
 
         (x1..=x2).contains(&x) && (y1..=y2).contains(&y)
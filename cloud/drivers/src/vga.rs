@@ -6,93 +6,86 @@ use std::{
 
 use common_types::{IMAGE_HEIGHT, IMAGE_WIDTH};
 use itertools::Itertools;
-use memmap2::{MmapOptions, MmapRaw};
-use voladdress::{Safe, VolAddress};
 
-use crate::{DevMem, Texture, LW_BRIDGE_BASE};
+use crate::{
+    devmem::{Region, Register},
+    font::{self, Align, TextStyle, GLYPH_SIZE},
+    texture::{self, Rect},
+    DevMem, Texture, LW_BRIDGE_BASE,
+};
 
 pub struct VgaDisplay {
-    control: MmapRaw,
-    buffer1: MmapRaw,
-    buffer2: MmapRaw,
-    char_buf: MmapRaw,
+    control: Region,
+    buffer1: Region,
+    buffer2: Region,
+    char_buf: Region,
     is_first: bool,
 }
 
-const PIXEL_BUF_CTRL_BASE: u64 = LW_BRIDGE_BASE + 0x00003020;
-const PIXEL_BUF_CTRL_SPAN: u64 = 0x00000010;
+pub(crate) const PIXEL_BUF_CTRL_BASE: u64 = LW_BRIDGE_BASE + 0x00003020;
+pub(crate) const PIXEL_BUF_CTRL_SPAN: u64 = 0x00000010;
+
+pub(crate) const PIXEL_BUF1_BASE: u64 = 0xC8000000;
+pub(crate) const PIXEL_BUF2_BASE: u64 = 0xC0000000;
+pub(crate) const PIXEL_BUF_SPAN: u64 = 0x0003FFFF;
 
-const PIXEL_BUF1_BASE: u64 = 0xC8000000;
-const PIXEL_BUF2_BASE: u64 = 0xC0000000;
-const PIXEL_BUF_SPAN: u64 = 0x0003FFFF;
+pub(crate) const CHAR_BUF_BASE: u64 = 0xC9000000;
+pub(crate) const CHAR_BUF_SPAN: u64 = 0x00001FFF;
 
-const CHAR_BUF_BASE: u64 = 0xC9000000;
-const CHAR_BUF_SPAN: u64 = 0x00001FFF;
+pub(crate) const PIXEL_BUF_WIDTH: usize = IMAGE_WIDTH;
+pub(crate) const PIXEL_BUF_HEIGHT: usize = IMAGE_HEIGHT;
 
-const PIXEL_BUF_WIDTH: usize = IMAGE_WIDTH;
-const PIXEL_BUF_HEIGHT: usize = IMAGE_HEIGHT;
+pub(crate) const CHAR_BUF_WIDTH: usize = 80;
+pub(crate) const CHAR_BUF_HEIGHT: usize = 60;
 
-const CHAR_BUF_WIDTH: usize = 80;
-const CHAR_BUF_HEIGHT: usize = 60;
+pub(crate) const DISPLAY_ENABLE: u32 = 1 << 2;
+pub(crate) const STATUS_FLAG: u32 = 1;
 
-const DISPLAY_ENABLE: u32 = 1 << 2;
-const STATUS_FLAG: u32 = 1;
+// register offsets from the control base
+pub(crate) const BUFFER_OFFSET: usize = 0;
+pub(crate) const BACK_BUFFER_OFFSET: usize = size_of::<u32>();
+pub(crate) const RESOLUTION_OFFSET: usize = size_of::<u32>() * 2;
+pub(crate) const STATUS_OFFSET: usize = size_of::<u32>() * 3;
 
 type Color = u16;
 
-struct VideoRegisters(usize);
+struct VideoRegisters<'a>(&'a Region);
 
-impl VideoRegisters {
+impl VideoRegisters<'_> {
     #[inline]
-    unsafe fn new(base: *const u8) -> Self {
-        Self(base as usize)
+    fn buffer(&self) -> Register<'_> {
+        self.0.reg(BUFFER_OFFSET)
     }
 
     #[inline]
-    fn buffer(&self) -> VolAddress<u32, Safe, Safe> {
-        unsafe { VolAddress::new(self.0) }
+    fn back_buffer(&self) -> Register<'_> {
+        self.0.reg(BACK_BUFFER_OFFSET)
     }
 
     #[inline]
-    fn back_buffer(&self) -> VolAddress<u32, Safe, Safe> {
-        unsafe { VolAddress::new(self.0 + size_of::<u32>()) }
+    fn status(&self) -> Register<'_> {
+        self.0.reg(STATUS_OFFSET)
     }
 
+    // writes go to the same register as the status
     #[inline]
-    fn status(&self) -> VolAddress<u32, Safe, ()> {
-        unsafe { VolAddress::new(self.0 + size_of::<u32>() * 3) }
-    }
-
-    #[inline]
-    fn control(&self) -> VolAddress<u32, (), Safe> {
-        unsafe { VolAddress::new(self.0 + size_of::<u32>() * 3) }
+    fn control(&self) -> Register<'_> {
+        self.0.reg(STATUS_OFFSET)
     }
 }
 
 impl VgaDisplay {
-    pub fn new(DevMem(mem): &DevMem) -> io::Result<Self> {
-        let control = MmapOptions::new()
-            .offset(PIXEL_BUF_CTRL_BASE)
-            .len(PIXEL_BUF_CTRL_SPAN as usize)
-            .map_raw(mem)?;
-        let buffer1 = MmapOptions::new()
-            .offset(PIXEL_BUF1_BASE)
-            .len(PIXEL_BUF_SPAN as usize)
-            .map_raw(mem)?;
-        let buffer2 = MmapOptions::new()
-            .offset(PIXEL_BUF2_BASE)
-            .len(PIXEL_BUF_SPAN as usize)
-            .map_raw(mem)?;
-        let char_buf = MmapOptions::new()
-            .offset(CHAR_BUF_BASE)
-            .len(CHAR_BUF_SPAN as usize)
-            .map_raw(mem)?;
+    pub fn new(mem: &DevMem) -> io::Result<Self> {
+        let control = mem.map(PIXEL_BUF_CTRL_BASE, PIXEL_BUF_CTRL_SPAN as usize)?;
+        let buffer1 = mem.map(PIXEL_BUF1_BASE, PIXEL_BUF_SPAN as usize)?;
+        let buffer2 = mem.map(PIXEL_BUF2_BASE, PIXEL_BUF_SPAN as usize)?;
+        let char_buf = mem.map(CHAR_BUF_BASE, CHAR_BUF_SPAN as usize)?;
 
         let is_first;
 
         // set up the addresses here
-        unsafe {
-            let regs = VideoRegisters::new(control.as_ptr());
+        {
+            let regs = VideoRegisters(&control);
 
             // enable first
             regs.control().write(regs.status().read() | DISPLAY_ENABLE);
@@ -136,17 +129,33 @@ impl VgaDisplay {
     }
 
     pub fn draw_texture(&mut self, x: usize, y: usize, texture: &Texture) {
-        // assertions to make sure we don't go out of bounds
-        assert!(x + texture.width() <= PIXEL_BUF_WIDTH);
-        assert!(y + texture.height() <= PIXEL_BUF_HEIGHT);
+        self.blit(x as isize, y as isize, texture, texture.rect());
+    }
+
+    // draws part of a texture with its top left at (x, y), which can be off the screen
+    // anything outside the screen is cut off, and transparent pixels are blended
+    pub fn blit(&mut self, x: isize, y: isize, texture: &Texture, src: Rect) {
+        assert!(src.x + src.width <= texture.width());
+        assert!(src.y + src.height <= texture.height());
 
         let buf_ptr = self.buf_ptr();
 
-        for ((y, x), color) in (y..y + texture.height())
-            .cartesian_product(x..x + texture.width())
-            .zip(texture.data().iter().copied())
-        {
-            Self::plot_on_buf(x, y, color, buf_ptr);
+        let x_range = x.max(0)..(x + src.width as isize).min(PIXEL_BUF_WIDTH as isize);
+        let y_range = y.max(0)..(y + src.height as isize).min(PIXEL_BUF_HEIGHT as isize);
+
+        for (py, px) in y_range.cartesian_product(x_range) {
+            let i = (src.y + (py - y) as usize) * texture.width() + src.x + (px - x) as usize;
+            let (px, py) = (px as usize, py as usize);
+            let color = texture.data()[i];
+
+            match texture.alpha().map(|alpha| alpha[i]) {
+                None | Some(u8::MAX) => Self::plot_on_buf(px, py, color, buf_ptr),
+                Some(0) => {}
+                Some(alpha) => {
+                    let under = Self::read_from_buf(px, py, buf_ptr);
+                    Self::plot_on_buf(px, py, texture::blend(color, under, alpha), buf_ptr);
+                }
+            }
         }
     }
 
@@ -256,6 +265,65 @@ impl VgaDisplay {
         }
     }
 
+    // draws text onto the pixels, wrapped to fit `width` pixels from x
+    // returns how tall the text came out, so that more can go below it
+    pub fn draw_text(
+        &mut self,
+        x: usize,
+        y: usize,
+        width: usize,
+        text: &str,
+        style: &TextStyle,
+    ) -> usize {
+        let TextStyle {
+            scale,
+            color,
+            background,
+            align,
+        } = *style;
+        assert!(scale > 0);
+
+        let cell = GLYPH_SIZE * scale;
+        let lines = font::wrap(text, (width / cell).max(1));
+        let buf_ptr = self.buf_ptr();
+
+        for (row, line) in lines.iter().enumerate() {
+            let spare = width.saturating_sub(line.chars().count() * cell);
+            let left = x + match align {
+                Align::Left => 0,
+                Align::Center => spare / 2,
+                Align::Right => spare,
+            };
+            let top = y + row * cell;
+
+            for (i, c) in line.chars().enumerate() {
+                let glyph = font::glyph(c);
+
+                for (gy, gx) in (0..GLYPH_SIZE).cartesian_product(0..GLYPH_SIZE) {
+                    let color = if glyph[gy] >> gx & 1 != 0 {
+                        color
+                    } else if let Some(background) = background {
+                        background
+                    } else {
+                        continue;
+                    };
+
+                    for (sy, sx) in (0..scale).cartesian_product(0..scale) {
+                        let px = left + i * cell + gx * scale + sx;
+                        let py = top + gy * scale + sy;
+
+                        // anything off the screen is cut off
+                        if px < PIXEL_BUF_WIDTH && py < PIXEL_BUF_HEIGHT {
+                            Self::plot_on_buf(px, py, color, buf_ptr);
+                        }
+                    }
+                }
+            }
+        }
+
+        lines.len() * cell
+    }
+
     pub fn erase_text(&mut self) {
         for x in 0..CHAR_BUF_WIDTH {
             for y in 0..CHAR_BUF_HEIGHT {
@@ -274,8 +342,8 @@ impl VgaDisplay {
     }
 
     pub async fn sync_screen(&mut self) {
-        unsafe {
-            let regs = VideoRegisters::new(self.control.as_ptr());
+        {
+            let regs = VideoRegisters(&self.control);
 
             regs.buffer().write(1);
 
@@ -295,13 +363,17 @@ impl VgaDisplay {
             (buf_ptr.add((x & 0x1FF) << 1 | (y & 0xFF) << 10) as *mut Color).write_volatile(color);
         }
     }
+
+    fn read_from_buf(x: usize, y: usize, buf_ptr: *mut u8) -> Color {
+        unsafe {
+            (buf_ptr.add((x & 0x1FF) << 1 | (y & 0xFF) << 10) as *const Color).read_volatile()
+        }
+    }
 }
 
 impl Drop for VgaDisplay {
     fn drop(&mut self) {
-        unsafe {
-            let regs = VideoRegisters::new(self.control.as_ptr());
-            regs.control().write(regs.status().read() & !DISPLAY_ENABLE);
-        }
+        let regs = VideoRegisters(&self.control);
+        regs.control().write(regs.status().read() & !DISPLAY_ENABLE);
     }
 }
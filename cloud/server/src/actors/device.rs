@@ -2,22 +2,30 @@ mod video;
 
 use crate::{
     actors::device::video::video_task,
+    constants::{BANDWIDTH_WINDOW, LIMIT_WARNING},
     types::{message::LinkMessage, state::AppState},
 };
-use std::{sync::Arc, time::Duration};
+use std::{
+    collections::{hash_map::Entry, HashMap},
+    sync::Arc,
+    time::Duration,
+};
 
 use anyhow::{bail, Context};
 use axum::extract::ws::{Message, WebSocket};
-use common_types::{DeviceId, DeviceResponse, VideoRequest};
+use bincode::Options;
+use common_types::{
+    Cue, DeviceId, DeviceRequest, DeviceResponse, ErrorCode, SessionId, VideoRequest, IMAGE_SIZE,
+    MAX_VIDEO_LENGTH,
+};
 use tokio::{
     select,
     sync::mpsc::{self, UnboundedSender},
+    time::Instant,
 };
 
 use self::video::VideoPart;
 
-const NAME_WIDTH: usize = 4;
-
 #[tracing::instrument(skip_all, err(Debug))]
 pub async fn device_task(
     state: Arc<AppState>,
@@ -37,13 +45,47 @@ enum DeviceState {
     Connected,
 }
 
-#[derive(Debug, Default)]
-enum VideoState {
-    #[default]
-    WaitStart,
-    WaitDone {
+enum VideoSession {
+    Active {
         video_tx: UnboundedSender<VideoPart>,
+        frames: usize,
+        started: Instant,
+        // whether the device was told it's about to run out of time
+        warned: bool,
     },
+    // refused for breaking a limit, so anything else sent for it gets dropped
+    Rejected,
+}
+
+// videos that have been started but not finished yet
+// each one has its own video task, so a device can have several in flight
+type VideoSessions = HashMap<SessionId, VideoSession>;
+
+// bytes received from a device during the current window
+struct Bandwidth {
+    window_start: Instant,
+    bytes: usize,
+}
+
+impl Bandwidth {
+    fn new() -> Self {
+        Self {
+            window_start: Instant::now(),
+            bytes: 0,
+        }
+    }
+
+    // returns whether the device is still within `limit` bytes per second
+    fn record(&mut self, bytes: usize, limit: usize) -> bool {
+        let now = Instant::now();
+        if now - self.window_start >= BANDWIDTH_WINDOW {
+            self.window_start = now;
+            self.bytes = 0;
+        }
+
+        self.bytes += bytes;
+        self.bytes <= limit * BANDWIDTH_WINDOW.as_secs() as usize
+    }
 }
 
 #[tracing::instrument(skip_all, err(Debug))]
@@ -58,7 +100,11 @@ async fn handle_device(
     // NOTE: we can't use a separate task because we still need to respond to pings
 
     let mut device_state = DeviceState::Disconnected;
-    let mut video_state = VideoState::WaitStart;
+    let mut sessions = VideoSessions::new();
+    let mut bandwidth = Bandwidth::new();
+
+    // video tasks report their errors and cues through here
+    let (res_tx, mut res_rx) = mpsc::unbounded_channel();
 
     loop {
         select! {
@@ -73,7 +119,24 @@ async fn handle_device(
                     break;
                 };
 
-                let msg = match msg? {
+                let msg = match msg {
+                    Ok(msg) => msg,
+                    Err(e) => {
+                        // most likely far too big, and the rest of it is still on the socket,
+                        // so there's no telling where the next message starts
+                        tracing::debug!("Failed to receive from {:?}: {e}", device_id);
+                        let res = DeviceResponse::Error {
+                            session_id: None,
+                            code: ErrorCode::MessageTooLarge,
+                            message: format!("Couldn't receive message: {e}"),
+                            recoverable: false,
+                        };
+                        _ = send_response(&mut ws, &res).await;
+                        break;
+                    }
+                };
+
+                let msg = match msg {
                     Message::Binary(msg) => {
                         tracing::debug!("Received device message from {:?}", device_id);
                         msg
@@ -91,7 +154,22 @@ async fn handle_device(
                     }
                 };
 
-                handle_ws_msg(app_state, msg, &device_id, &mut video_state)?;
+                let res = handle_ws_msg(
+                    app_state,
+                    msg,
+                    &device_id,
+                    &mut sessions,
+                    &mut bandwidth,
+                    &res_tx,
+                );
+                if let Some(res) = res {
+                    send_response(&mut ws, &res).await?;
+                }
+            }
+            res = res_rx.recv() => {
+                // we hold on to a sender, so this can't be None
+                let res = res.context("Video tasks stopped")?;
+                send_response(&mut ws, &res).await?;
             }
             msg = device_rx.recv() => {
                 let msg = msg.context("Link task stopped")?;
@@ -119,29 +197,115 @@ async fn handle_device_msg(
             tracing::debug!("{:?} disconnected", device_id);
             *state = DeviceState::Disconnected;
         }
+        (
+            DeviceState::Connected,
+            DeviceResponse::SelectWorkout { .. }
+            | DeviceResponse::StartRecording
+            | DeviceResponse::StopRecording,
+        ) => {
+            tracing::debug!("Forwarding {:?} to {:?}", msg, device_id);
+        }
         (_, msg) => {
             bail!("Unexpected device response: {:?}", msg)
         }
     };
 
-    let res = serde_json::to_string(&msg)?;
+    send_response(ws, &msg).await
+}
+
+async fn send_response(ws: &mut WebSocket, res: &DeviceResponse) -> anyhow::Result<()> {
+    let res = serde_json::to_string(res)?;
     ws.send(Message::Text(res)).await?;
 
     Ok(())
 }
 
+// returns a response for the device if the message was refused, or it needs warning
+// a message that can't be handled only costs the device its session, not the connection
 fn handle_ws_msg(
     app_state: &Arc<AppState>,
     msg: Vec<u8>,
     device_id: &DeviceId,
-    state: &mut VideoState,
-) -> anyhow::Result<()> {
-    let req: VideoRequest = bincode::deserialize(&msg)?;
-    tracing::debug!("Received video request from {:?}: {:?}", device_id, req);
+    sessions: &mut VideoSessions,
+    bandwidth: &mut Bandwidth,
+    res_tx: &UnboundedSender<DeviceResponse>,
+) -> Option<DeviceResponse> {
+    let limits = &app_state.limits;
+    let within_bandwidth = bandwidth.record(msg.len(), limits.max_bandwidth);
+
+    // same options as bincode::deserialize, but with a limit so that a bogus length
+    // can't make us allocate more than a message could ever hold
+    let options = bincode::DefaultOptions::new()
+        .with_fixint_encoding()
+        .allow_trailing_bytes()
+        .with_limit(limits.max_message_size as u64);
+
+    let (code, message) = if msg.len() > limits.max_message_size {
+        (
+            ErrorCode::MessageTooLarge,
+            format!(
+                "Message is {} bytes, at most {} are allowed",
+                msg.len(),
+                limits.max_message_size
+            ),
+        )
+    } else {
+        match options.deserialize(&msg) {
+            Ok(req) => {
+                return handle_request(
+                    app_state,
+                    req,
+                    device_id,
+                    sessions,
+                    within_bandwidth,
+                    res_tx,
+                )
+            }
+            Err(e) => (ErrorCode::BadFrame, format!("Couldn't decode request: {e}")),
+        }
+    };
+
+    // the session id comes first, so it can usually be made out even if the rest can't
+    let session_id = options.deserialize::<SessionId>(&msg).ok();
+    if let Some(session_id) = &session_id {
+        tracing::debug!("Refused {:?} from {:?}: {message}", session_id, device_id);
+        // only ones that were started, so garbage under new ids can't pile up
+        if let Some(session) = sessions.get_mut(session_id) {
+            // video_tx gets dropped if there is one, which deletes the video
+            *session = VideoSession::Rejected;
+        }
+    } else {
+        tracing::debug!("Refused message from {:?}: {message}", device_id);
+    }
+
+    Some(DeviceResponse::Error {
+        session_id,
+        code,
+        message,
+        recoverable: false,
+    })
+}
+
+// returns a response for the device if the request was refused, or it needs warning
+fn handle_request(
+    app_state: &Arc<AppState>,
+    DeviceRequest { session_id, req }: DeviceRequest,
+    device_id: &DeviceId,
+    sessions: &mut VideoSessions,
+    within_bandwidth: bool,
+    res_tx: &UnboundedSender<DeviceResponse>,
+) -> Option<DeviceResponse> {
+    let limits = &app_state.limits;
+    tracing::debug!(
+        "Received video request from {:?} for {:?}: {:?}",
+        device_id,
+        session_id,
+        req
+    );
 
-    match (std::mem::take(state), req) {
+    match (sessions.entry(session_id), req) {
         (
-            VideoState::WaitStart,
+            Entry::Vacant(v),
             VideoRequest::Start {
                 user_id,
                 workout_type,
@@ -151,26 +315,118 @@ fn handle_ws_msg(
             tokio::spawn(video_task(
                 app_state.clone(),
                 video_rx,
+                res_tx.clone(),
+                device_id.clone(),
+                v.key().clone(),
                 user_id,
                 workout_type,
             ));
-            *state = VideoState::WaitDone { video_tx };
+            v.insert(VideoSession::Active {
+                video_tx,
+                frames: 0,
+                started: Instant::now(),
+                warned: false,
+            });
+        }
+        (Entry::Occupied(mut o), VideoRequest::Frames(frames)) => {
+            let VideoSession::Active {
+                video_tx,
+                frames: count,
+                started,
+                warned,
+            } = o.get_mut()
+            else {
+                // already refused, the device just doesn't know yet
+                return None;
+            };
+
+            let refusal = if !within_bandwidth {
+                Some((
+                    ErrorCode::BandwidthExceeded,
+                    format!("Sent more than {} bytes/s", limits.max_bandwidth),
+                ))
+            } else if frames.len() > limits.max_batch_frames {
+                Some((
+                    ErrorCode::TooManyFrames,
+                    format!(
+                        "Sent {} frames at once, at most {} are allowed",
+                        frames.len(),
+                        limits.max_batch_frames
+                    ),
+                ))
+            } else if let Some(frame) = frames.iter().find(|f| f.0.len() != IMAGE_SIZE) {
+                Some((
+                    ErrorCode::BadFrame,
+                    format!("Frame is {} bytes, expected {IMAGE_SIZE}", frame.0.len()),
+                ))
+            } else if *count + frames.len() > limits.max_video_frames {
+                Some((
+                    ErrorCode::VideoTooLong,
+                    format!("Video has more than {} frames", limits.max_video_frames),
+                ))
+            } else if started.elapsed() > limits.max_video_length {
+                Some((
+                    ErrorCode::VideoTooLong,
+                    format!("Video is longer than {:?}", limits.max_video_length),
+                ))
+            } else {
+                None
+            };
+
+            if let Some((code, message)) = refusal {
+                tracing::debug!("Refused {:?} from {:?}: {message}", o.key(), device_id);
+                // video_tx gets dropped, which deletes the video
+                let session_id = Some(o.key().clone());
+                o.insert(VideoSession::Rejected);
+                return Some(DeviceResponse::Error {
+                    session_id,
+                    code,
+                    message,
+                    recoverable: false,
+                });
+            }
+
+            *count += frames.len();
+            if video_tx.send(VideoPart::Frames(frames)).is_err() {
+                // video task failed, and has already told the device why
+                o.insert(VideoSession::Rejected);
+            } else if !*warned
+                && started.elapsed() + LIMIT_WARNING
+                    >= limits.max_video_length.min(MAX_VIDEO_LENGTH)
+            {
+                // whichever comes first of the device stopping itself and us cutting it off
+                // checked as frames come in, which they do every batch while recording
+                *warned = true;
+                return Some(DeviceResponse::Cue {
+                    session_id: o.key().clone(),
+                    cue: Cue::NearLimit,
+                });
+            }
         }
-        (VideoState::WaitDone { video_tx }, VideoRequest::Frames(frames)) => {
-            video_tx.send(VideoPart::Frames(frames))?;
-            *state = VideoState::WaitDone { video_tx };
+        (Entry::Occupied(o), VideoRequest::Done) => {
+            if let VideoSession::Active { video_tx, .. } = o.remove() {
+                // if the video task failed, it has already told the device why
+                _ = video_tx.send(VideoPart::Done);
+                // video_tx gets dropped, but video is done so it will get processed
+            }
         }
-        (VideoState::WaitDone { video_tx }, VideoRequest::Done) => {
-            video_tx.send(VideoPart::Done)?;
-            // video_tx gets dropped, but video is done so it will get processed
+        (Entry::Occupied(o), VideoRequest::Cancel) => {
+            // video_tx gets dropped if there is one, which deletes the video
+            o.remove();
         }
-        (_, VideoRequest::Cancel) => {
-            // state becomes WaitStart, video_tx gets dropped if it exists
+        (Entry::Vacant(_), VideoRequest::Cancel) => {
+            // video already finished or never started, nothing to cancel
         }
-        (state, req) => {
-            bail!("Invalid: (state, req) = ({state:?}, {req:?})")
+        (entry, req) => {
+            tracing::debug!("Invalid: (session, req) = ({:?}, {req:?})", entry.key());
+            return Some(DeviceResponse::Error {
+                session_id: Some(entry.key().clone()),
+                code: ErrorCode::InvalidState,
+                message: format!("Can't handle {req:?} for {}", entry.key()),
+                recoverable: false,
+            });
         }
     }
 
-    Ok(())
+    None
 }
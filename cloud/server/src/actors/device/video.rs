@@ -1,20 +1,28 @@
 use std::sync::Arc;
 
-use anyhow::Context;
-use common_types::{Feedback, Frame, UserId, VideoId, WorkoutType, IMAGE_HEIGHT, IMAGE_WIDTH};
-use firestore::{struct_path::paths, FirestoreDb, FirestoreTimestamp, ParentPathBuilder};
-use google_cloud_storage::{
-    client::Client as StorageClient,
-    http::objects::upload::{Media, UploadObjectRequest, UploadType},
+use common_types::{
+    Cue, DeviceId, DeviceResponse, ErrorCode, Frame, SessionId, UserId, VideoId, WorkoutType,
+};
+use firestore::{struct_path::paths, ParentPathBuilder};
+use thiserror::Error;
+use tokio::{
+    fs::File,
+    io::AsyncWriteExt,
+    sync::mpsc::{UnboundedReceiver, UnboundedSender},
+    time::Instant,
 };
-use image::{ImageBuffer, RgbImage};
-use rayon::prelude::{IndexedParallelIterator, IntoParallelIterator, ParallelIterator};
-use rgb565::Rgb565;
-use serde::{Deserialize, Serialize};
-use tokio::{process::Command, sync::mpsc::UnboundedReceiver};
 use uuid::Uuid;
 
-use crate::{actors::device::NAME_WIDTH, constants::*, types::state::AppState};
+use crate::{
+    analyzer::{apply_analysis, call_ml, save_frames},
+    constants::*,
+    profile::get_profile,
+    storage::{upload_footage, upload_landmarks, upload_video},
+    types::state::AppState,
+    workout::{
+        now, parent_path, record_failure, update_entry, upload_entry, WorkoutEntry, WorkoutStatus,
+    },
+};
 
 #[derive(Debug)]
 pub(super) enum VideoPart {
@@ -22,19 +30,151 @@ pub(super) enum VideoPart {
     Done,
 }
 
+// failures that get reported back to the device
+#[derive(Debug, Error)]
+pub(super) enum VideoError {
+    #[error("Storage failure: {0:#}")]
+    Storage(anyhow::Error),
+    #[error("Analyzer failure: {0:#}")]
+    Analyzer(anyhow::Error),
+}
+
+impl VideoError {
+    fn storage(e: impl Into<anyhow::Error>) -> Self {
+        Self::Storage(e.into())
+    }
+
+    fn analyzer(e: impl Into<anyhow::Error>) -> Self {
+        Self::Analyzer(e.into())
+    }
+
+    fn code(&self) -> ErrorCode {
+        match self {
+            VideoError::Storage(..) => ErrorCode::StorageFailure,
+            VideoError::Analyzer(..) => ErrorCode::AnalyzerFailure,
+        }
+    }
+}
+
 #[tracing::instrument(skip_all, err(Debug))]
 pub(super) async fn video_task(
     state: Arc<AppState>,
     mut video_rx: UnboundedReceiver<VideoPart>,
+    res_tx: UnboundedSender<DeviceResponse>,
+    device_id: DeviceId,
+    session_id: SessionId,
     user_id: UserId,
     workout_type: WorkoutType,
-) -> anyhow::Result<()> {
-    // create video folder
+) -> Result<(), VideoError> {
     let video_id = VideoId::from(Uuid::new_v4().to_string());
     let folder_path: Arc<str> = format!("{VIDEO_PATH}/{video_id}.d").into();
+    let started = Instant::now();
+
+    // without an entry there's nowhere to record anything, so just tell the device
+    let entry = async {
+        let parent_path = parent_path(&state.db, &user_id)?;
+        let entry = upload_entry(&state.db, &parent_path, device_id, workout_type).await?;
+        anyhow::Ok((parent_path, entry))
+    }
+    .await
+    .map_err(VideoError::storage);
+    let (parent_path, mut entry) = match entry {
+        Ok(entry) => entry,
+        Err(e) => return Err(report_error(&res_tx, session_id, e, false)),
+    };
+    tracing::debug!("Uploaded recording entry for {video_id:?}");
+
+    let footage_path = state
+        .keep_footage
+        .then(|| format!("{folder_path}/{FOOTAGE_NAME}"));
+
+    // failing while receiving loses the video, but once it's done the device can carry on
+    let res = match receive_video(&mut video_rx, &folder_path, footage_path.as_deref()).await {
+        Ok(Some(frame_count)) => {
+            entry.frame_count = Some(frame_count);
+            entry.duration = Some(started.elapsed().as_secs_f64());
+            handle_video(
+                &state,
+                &user_id,
+                &parent_path,
+                &mut entry,
+                &video_id,
+                &folder_path,
+                footage_path.as_deref(),
+            )
+            .await
+            .map(|()| {
+                // the device might be gone by now, which is fine
+                _ = res_tx.send(DeviceResponse::Cue {
+                    session_id: session_id.clone(),
+                    cue: Cue::Analyzed,
+                });
+            })
+            .map_err(|e| (e, true))
+        }
+        Ok(None) => {
+            // nothing went wrong, but the entry shouldn't be left recording forever
+            record_failure(
+                &state.db,
+                &parent_path,
+                &mut entry,
+                "Recording was cancelled".into(),
+            )
+            .await
+            .map_err(|e| (VideoError::storage(e), true))
+        }
+        Err(e) => Err((e, false)),
+    };
+
+    let Err((e, recoverable)) = res else {
+        return Ok(());
+    };
+
+    // let the app know why there won't be any feedback
+    if !matches!(entry.status, WorkoutStatus::Failed { .. }) {
+        if let Err(e) = record_failure(&state.db, &parent_path, &mut entry, e.to_string()).await {
+            tracing::warn!("Failed to record failure for {video_id:?}: {e:?}");
+        }
+    }
+
+    Err(report_error(&res_tx, session_id, e, recoverable))
+}
+
+fn report_error(
+    res_tx: &UnboundedSender<DeviceResponse>,
+    session_id: SessionId,
+    e: VideoError,
+    recoverable: bool,
+) -> VideoError {
+    // the device might be gone by now, in which case there's no one to tell
+    _ = res_tx.send(DeviceResponse::Error {
+        session_id: Some(session_id),
+        code: e.code(),
+        message: e.to_string(),
+        recoverable,
+    });
+
+    e
+}
 
-    tracing::debug!("Creating folder: {}", &*folder_path);
-    tokio::fs::create_dir(&*folder_path).await?;
+// saves frames until the video is done and returns how many there were,
+// or None if it was cancelled instead
+// if there's a footage path, the raw frames are also appended to it
+async fn receive_video(
+    video_rx: &mut UnboundedReceiver<VideoPart>,
+    folder_path: &Arc<str>,
+    footage_path: Option<&str>,
+) -> Result<Option<u32>, VideoError> {
+    // create video folder
+    tracing::debug!("Creating folder: {}", &**folder_path);
+    tokio::fs::create_dir(&**folder_path)
+        .await
+        .map_err(VideoError::storage)?;
+
+    let mut footage = match footage_path {
+        Some(path) => Some(File::create(path).await.map_err(VideoError::storage)?),
+        None => None,
+    };
 
     // number of frames received
     let mut count = 0;
@@ -42,233 +182,122 @@ pub(super) async fn video_task(
     loop {
         match video_rx.recv().await {
             Some(VideoPart::Frames(frames)) => {
-                // save all images
-                // combine oneshot with rayon
-                let (send, recv) = tokio::sync::oneshot::channel();
-                let len = frames.len();
-                let folder_path = folder_path.clone();
-
-                rayon::spawn(move || {
-                    let res = frames.into_par_iter().enumerate().try_for_each(
-                        |(i, frame)| -> anyhow::Result<()> {
-                            let img =
-                                frame_to_image(frame, IMAGE_HEIGHT as u32, IMAGE_WIDTH as u32)?;
-                            let filename =
-                                format!("{folder_path}/{j:0NAME_WIDTH$}.png", j = count + i);
-                            img.save(&filename)
-                                .context(format!("Failed to save file: {filename}"))?;
-
-                            Ok(())
-                        },
-                    );
-
-                    _ = send.send(res);
-                });
+                if let Some(footage) = &mut footage {
+                    for Frame(buf) in &frames {
+                        footage.write_all(buf).await.map_err(VideoError::storage)?;
+                    }
+                }
 
-                // acts like an async join
-                recv.await??;
+                let len = frames.len();
+                save_frames(folder_path.clone(), frames, count)
+                    .await
+                    .map_err(VideoError::Storage)?;
 
                 count += len;
             }
             Some(VideoPart::Done) => {
-                handle_video(state, user_id, video_id, &folder_path, workout_type).await?;
-                break;
+                if let Some(footage) = &mut footage {
+                    footage.flush().await.map_err(VideoError::storage)?;
+                }
+
+                return Ok(Some(count as u32));
             }
             None => {
                 // connection dropped, delete video folder
-                tracing::debug!("Deleting folder: {}", &*folder_path);
-                tokio::fs::remove_dir_all(&*folder_path).await?;
+                tracing::debug!("Deleting folder: {}", &**folder_path);
+                tokio::fs::remove_dir_all(&**folder_path)
+                    .await
+                    .map_err(VideoError::storage)?;
 
-                // remember to exit the loop!
-                break;
+                return Ok(None);
             }
         }
     }
-
-    Ok(())
 }
 
 async fn handle_video(
-    state: Arc<AppState>,
-    user_id: UserId,
-    video_id: VideoId,
+    state: &AppState,
+    user_id: &UserId,
+    parent_path: &ParentPathBuilder,
+    entry: &mut WorkoutEntry,
+    video_id: &VideoId,
     folder_path: &str,
-    workout_type: WorkoutType,
-) -> anyhow::Result<()> {
-    let date = FirestoreTimestamp::from(chrono::offset::Utc::now());
-
-    let parent_path = state.db.parent_path(USER_COLLECTION, user_id.as_ref())?;
-
+    footage_path: Option<&str>,
+) -> Result<(), VideoError> {
+    entry.status = WorkoutStatus::Processing;
+    entry.processing_at = Some(now());
+    update_entry(
+        &state.db,
+        parent_path,
+        entry,
+        paths!(WorkoutEntry::{status, processing_at, duration, frame_count}),
+    )
+    .await
+    .map_err(VideoError::storage)?;
     tracing::debug!("Started processing {video_id:?}");
 
-    let entry = upload_entry(&state.db, &parent_path, date, workout_type).await?;
-    tracing::debug!("Uploaded empty entry for {video_id:?}");
-
-    let feedback = call_ml(&video_id, folder_path, workout_type).await?;
-
-    upload_feedback(&state.db, &parent_path, entry, &video_id, feedback).await?;
-    tracing::debug!("Uploaded feedback for {video_id:?}");
-
     let video_path = format!("{VIDEO_PATH}/{video_id}.mp4");
-    upload_video(&state.client, &video_id, &video_path).await?;
+    let profile = get_profile(&state.db, user_id)
+        .await
+        .map_err(VideoError::storage)?
+        .unwrap_or_default();
+    let analysis = call_ml(
+        &video_path,
+        folder_path,
+        entry.workout_type,
+        profile.rep_angle(entry.workout_type),
+    )
+    .await
+    .map_err(VideoError::analyzer)?;
+
+    upload_video(&state.client, video_id, &video_path)
+        .await
+        .map_err(VideoError::storage)?;
     tracing::debug!("Uploaded video for {video_id:?}");
 
-    // delete video file and folder
-    tracing::debug!("Deleting video {:?} and folder {}", video_id, &folder_path);
-    tokio::fs::remove_file(video_path).await?;
-    tokio::fs::remove_dir_all(folder_path).await?;
-
-    Ok(())
-}
-
-fn frame_to_image(Frame(buf): Frame, height: u32, width: u32) -> anyhow::Result<RgbImage> {
-    // need to convert little endian to rgb
-    let buf: Vec<_> = buf
-        .chunks_exact(2)
-        .flat_map(|c| Rgb565::from_rgb565_le([c[0], c[1]]).to_rgb888_components())
-        .collect();
-
-    ImageBuffer::from_vec(width, height, buf).context("Failed to create image")
-}
-
-async fn call_ml(
-    video_id: &VideoId,
-    folder_path: &str,
-    workout_type: WorkoutType,
-) -> anyhow::Result<Vec<Feedback>> {
-    let ml_path = match workout_type {
-        WorkoutType::Squat => "./.ml/squatPredictor.py",
-        WorkoutType::Pushup => "./.ml/pushupPredictor.py",
-    };
-
-    let res = Command::new("python")
-        .args([
-            ml_path,
-            &format!("{folder_path}/%0{NAME_WIDTH}d.png"),
-            &format!("{VIDEO_PATH}/{video_id}.mp4"),
-        ])
-        .output()
-        .await?;
-
-    tracing::debug!(
-        "stdout from {ml_path}: {}",
-        String::from_utf8_lossy(&res.stdout)
-    );
-    tracing::debug!(
-        "stderr from {ml_path}: {}",
-        String::from_utf8_lossy(&res.stderr)
-    );
-
-    Ok(serde_json::from_slice(&res.stdout)?)
-}
-
-async fn upload_video(
-    client: &StorageClient,
-    video_id: &VideoId,
-    video_path: &str,
-) -> anyhow::Result<()> {
-    tracing::debug!("Video path is: {video_path}");
-    let video = tokio::fs::read(&video_path).await?;
-
-    let upload_type = UploadType::Simple(Media {
-        name: format!("videos/{video_id}").into(),
-        content_type: "video/mp4".into(),
-        content_length: None,
-    });
-
-    // NOTE: using firebase emulators:exec breaks this for some reason!!!
-    client
-        .upload_object(
-            &UploadObjectRequest {
-                bucket: BUCKET_NAME.into(),
-                ..Default::default()
-            },
-            video,
-            &upload_type,
-        )
-        .await?;
-
-    Ok(())
-}
-
-#[derive(Serialize, Deserialize, Debug)]
-struct WorkoutEntry {
-    #[serde(alias = "_firestore_id")]
-    id: Option<String>,
-    date: FirestoreTimestamp,
-    #[serde(rename = "type")]
-    workout_type: WorkoutType,
-    video_id: Option<VideoId>,
-    reps: Option<Vec<Feedback>>,
-}
-
-// uploads the workout entry without video id or feedback
-async fn upload_entry(
-    db: &FirestoreDb,
-    parent_path: &ParentPathBuilder,
-    date: FirestoreTimestamp,
-    workout_type: WorkoutType,
-) -> anyhow::Result<WorkoutEntry> {
-    let entry = WorkoutEntry {
-        id: None,
-        date,
-        workout_type,
-        video_id: None,
-        reps: None,
-    };
-
-    let entry = db
-        .fluent()
-        .insert()
-        .into(WORKOUT_COLLECTION)
-        .generate_document_id()
-        .parent(parent_path)
-        .object(&entry)
-        .execute::<WorkoutEntry>()
-        .await?;
-
-    Ok(entry)
-}
+    // landmarks line up with the video, so they share its ID
+    if let Some(landmarks) = &analysis.landmarks {
+        upload_landmarks(&state.client, video_id, landmarks)
+            .await
+            .map_err(VideoError::storage)?;
+        entry.landmarks_id = Some(video_id.clone());
+        tracing::debug!("Uploaded landmarks for {video_id:?}");
+    }
 
-async fn upload_feedback(
-    db: &FirestoreDb,
-    parent_path: &ParentPathBuilder,
-    entry: WorkoutEntry,
-    video_id: &VideoId,
-    feedback: Vec<Feedback>,
-) -> anyhow::Result<()> {
-    let entry = WorkoutEntry {
-        video_id: Some(video_id.clone()),
-        reps: Some(feedback),
-        ..entry
-    };
+    // the footage goes under the same ID as the first video made from it
+    if let Some(footage_path) = footage_path {
+        upload_footage(&state.client, video_id, footage_path)
+            .await
+            .map_err(VideoError::storage)?;
+        entry.footage_id = Some(video_id.clone());
+        tracing::debug!("Uploaded footage for {video_id:?}");
+    }
 
-    db.fluent()
-        .update()
-        .fields(paths!(WorkoutEntry::{video_id, reps}))
-        .in_col(WORKOUT_COLLECTION)
-        .document_id(entry.id.as_ref().unwrap())
-        .parent(parent_path)
-        .object(&entry)
-        .execute::<WorkoutEntry>()
-        .await?;
+    // delete video file and folder
+    tracing::debug!("Deleting video {:?} and folder {}", video_id, &folder_path);
+    tokio::fs::remove_file(video_path)
+        .await
+        .map_err(VideoError::storage)?;
+    tokio::fs::remove_dir_all(folder_path)
+        .await
+        .map_err(VideoError::storage)?;
+
+    // only point the app at the video once it's actually uploaded
+    entry.status = WorkoutStatus::Completed;
+    entry.completed_at = Some(now());
+    entry.video_id = Some(video_id.clone());
+    apply_analysis(state, entry, analysis, &profile)
+        .await
+        .map_err(VideoError::storage)?;
+    update_entry(
+        &state.db,
+        parent_path,
+        entry,
+        paths!(WorkoutEntry::{status, completed_at, footage_id, analyzer, video_id, landmarks_id, reps, metrics, insight, hold}),
+    )
+    .await
+    .map_err(VideoError::storage)?;
+    tracing::debug!("Uploaded feedback for {video_id:?}");
 
     Ok(())
 }
-
-#[cfg(test)]
-mod test {
-    use crate::open_storage;
-
-    use super::*;
-    #[tokio::test]
-    async fn test_video_upload() -> anyhow::Result<()> {
-        let client = open_storage().await;
-        let video_id = VideoId::from(Uuid::new_v4().to_string());
-        let video_path = "../.video/test.mp4";
-
-        upload_video(&client, &video_id, video_path).await?;
-
-        Ok(())
-    }
-}
@@ -169,11 +169,53 @@ impl LinkManager {
                 device_entry.connection = DeviceConnection::Disconnected;
                 log_if_err!(device_entry.res_tx.send(DeviceResponse::Disconnected).await);
             }
+            LinkRequest::SelectWorkout { workout_type } => {
+                self.forward_command(user_id, DeviceResponse::SelectWorkout { workout_type })
+                    .await?
+            }
+            LinkRequest::StartRecording => {
+                self.forward_command(user_id, DeviceResponse::StartRecording)
+                    .await?
+            }
+            LinkRequest::StopRecording => {
+                self.forward_command(user_id, DeviceResponse::StopRecording)
+                    .await?
+            }
         }
 
         Ok(())
     }
 
+    // relays a command from the user to whichever device it is connected to
+    async fn forward_command(&mut self, user_id: UserId, cmd: DeviceResponse) -> LinkResult<()> {
+        tracing::debug!("{user_id:?} sent command {cmd:?}");
+
+        let user_entry = self.users.get(&user_id).ok_or(LinkError::NoUserEntry)?;
+
+        let device_id = match &user_entry.connection {
+            UserConnection::Connected(device_id) => device_id,
+            UserConnection::Dropped => {
+                // device is already gone, so there is no one to forward to
+                return Ok(());
+            }
+            UserConnection::Disconnected => return Err(LinkError::DisconnectedUser),
+        };
+
+        let device_entry = self
+            .devices
+            .get(device_id)
+            .ok_or(LinkError::NoDeviceEntry)?;
+
+        match &device_entry.connection {
+            DeviceConnection::Connected(id) if *id == user_id => (),
+            _ => return Err(LinkError::NoMatchingUser),
+        }
+
+        log_if_err!(device_entry.res_tx.send(cmd).await);
+
+        Ok(())
+    }
+
     async fn handle_new_user(&mut self, NewUser { user_id, res_tx }: NewUser) {
         tracing::debug!("{user_id:?} connected");
 
@@ -115,6 +115,23 @@ async fn handle_ws_msg(
                 .await?;
             *state = UserState::PendingDisconnect;
         }
+        (
+            UserState::Connected,
+            LinkRequest::SelectWorkout { .. }
+            | LinkRequest::StartRecording
+            | LinkRequest::StopRecording,
+        ) => {
+            tracing::debug!("{:?} sent device command {:?}", user_id, req);
+            link_tx
+                .send(
+                    UserLink {
+                        user_id: user_id.clone(),
+                        req,
+                    }
+                    .into(),
+                )
+                .await?;
+        }
         // this has the potential of going wrong
         // let's log it for now and see what happens...
         (state, req) => {
@@ -0,0 +1,40 @@
+pub mod fatigue;
+pub mod landmarks;
+pub mod metrics;
+pub mod plank;
+pub mod squat;
+pub mod trajectory;
+
+use serde::{Deserialize, Serialize};
+
+/// A pose landmark from mediapipe, in normalized image coordinates.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq)]
+pub struct Landmark {
+    pub x: f64,
+    pub y: f64,
+    pub visibility: f64,
+}
+
+type Point = (f64, f64);
+
+impl Landmark {
+    fn point(&self) -> Point {
+        (self.x, self.y)
+    }
+}
+
+// angle at b in degrees, between 0 and 180
+pub fn calculate_angle(a: Point, b: Point, c: Point) -> f64 {
+    let radians = (c.1 - b.1).atan2(c.0 - b.0) - (a.1 - b.1).atan2(a.0 - b.0);
+    let angle = radians.to_degrees().abs();
+
+    if angle > 180.0 {
+        360.0 - angle
+    } else {
+        angle
+    }
+}
+
+pub fn calculate_distance(a: Point, b: Point) -> f64 {
+    (a.0 - b.0).hypot(a.1 - b.1)
+}
@@ -0,0 +1,170 @@
+use common_types::{Feedback, SetInsight};
+
+// what both analyzers call a rep without faults
+const GOOD_CLASS: &str = "Acceptable";
+// fewer reps than this aren't enough to call a trend
+const MIN_REPS: usize = 4;
+// reps needed either side of a breakdown, so one bad rep doesn't count as one
+const MIN_SEGMENT: usize = 2;
+// share of faulty reps allowed before a breakdown, and needed after it
+const MAX_FAULTS_BEFORE: f64 = 0.25;
+const MIN_FAULTS_AFTER: f64 = 0.75;
+// how much shallower in degrees the reps need to get over the set
+const MIN_DEPTH_LOSS: f64 = 10.0;
+// how much slower the reps need to come up over the set, as a fraction
+const MIN_SLOWDOWN: f64 = 0.3;
+
+/// Looks for form getting worse as the set went on, which reps judged on their
+/// own don't show.
+pub fn set_insight(reps: &[Feedback]) -> Option<SetInsight> {
+    if reps.len() < MIN_REPS {
+        return None;
+    }
+
+    let breakdown_after = breakdown(reps);
+
+    // the metrics are only there for reps that were measured
+    let depths: Vec<_> = reps
+        .iter()
+        .filter_map(|rep| Some(rep.metrics?.min_knee_angle))
+        .collect();
+    let depth_loss = (depths.len() >= MIN_REPS)
+        .then(|| trend(&depths).1)
+        .filter(|&loss| loss >= MIN_DEPTH_LOSS);
+
+    let concentrics: Vec<_> = reps
+        .iter()
+        .filter_map(|rep| Some(rep.metrics?.concentric_secs))
+        .collect();
+    let slowdown = (concentrics.len() >= MIN_REPS)
+        .then(|| trend(&concentrics))
+        .filter(|&(first, _)| first > 0.0)
+        .map(|(first, change)| change / first)
+        .filter(|&slowdown| slowdown >= MIN_SLOWDOWN);
+
+    let message = if let Some(rep) = breakdown_after {
+        format!("Form broke down after rep {rep}, consider a lighter load")
+    } else if let Some(loss) = depth_loss {
+        format!("Reps got {loss:.0}° shallower over the set, consider a lighter load")
+    } else {
+        // nothing worth saying about the set otherwise
+        let slowdown = slowdown?;
+        format!(
+            "Reps came up {:.0}% slower by the end of the set, you were close to your limit",
+            slowdown * 100.0
+        )
+    };
+
+    Some(SetInsight {
+        breakdown_after,
+        depth_loss,
+        slowdown,
+        message,
+    })
+}
+
+// the last rep before the share of faulty reps jumped the most, counting from 1
+fn breakdown(reps: &[Feedback]) -> Option<u32> {
+    let faults: Vec<_> = reps.iter().map(|rep| rep.class != GOOD_CLASS).collect();
+    let rate = |faults: &[bool]| {
+        faults.iter().filter(|&&fault| fault).count() as f64 / faults.len() as f64
+    };
+
+    (MIN_SEGMENT..=faults.len() - MIN_SEGMENT)
+        .map(|split| {
+            let (before, after) = faults.split_at(split);
+            (split, rate(before), rate(after))
+        })
+        .filter(|&(_, before, after)| before <= MAX_FAULTS_BEFORE && after >= MIN_FAULTS_AFTER)
+        .max_by(|a, b| (a.2 - a.1).total_cmp(&(b.2 - b.1)))
+        .map(|(split, ..)| split as u32)
+}
+
+// least squares line through the values, as where it starts and how much it
+// changes from the first to the last
+fn trend(values: &[f64]) -> (f64, f64) {
+    let n = values.len() as f64;
+    let mean_x = (n - 1.0) / 2.0;
+    let mean_y = values.iter().sum::<f64>() / n;
+
+    let (covariance, variance) =
+        values
+            .iter()
+            .enumerate()
+            .fold((0.0, 0.0), |(covariance, variance), (x, y)| {
+                let dx = x as f64 - mean_x;
+                (covariance + dx * (y - mean_y), variance + dx * dx)
+            });
+    let slope = if variance > 0.0 {
+        covariance / variance
+    } else {
+        0.0
+    };
+
+    (mean_y - slope * mean_x, slope * (n - 1.0))
+}
+
+#[cfg(test)]
+mod test {
+    use common_types::RepMetrics;
+
+    use super::*;
+
+    fn rep(class: &str, min_knee_angle: f64, concentric_secs: f64) -> Feedback {
+        Feedback {
+            class: class.into(),
+            correction: String::new(),
+            confidence: None,
+            start_frame: None,
+            bottom_frame: None,
+            end_frame: None,
+            metrics: Some(RepMetrics {
+                eccentric_secs: 1.0,
+                concentric_secs,
+                min_knee_angle,
+                hip_angle_at_bottom: None,
+                asymmetry: 0.0,
+            }),
+            form_score: None,
+            worst_phase: None,
+        }
+    }
+
+    #[test]
+    fn test_breakdown() {
+        let reps: Vec<_> = (0..12)
+            .map(|i| match i {
+                0..=7 => rep("Acceptable", 85.0, 1.0),
+                _ => rep("Half Squat", 85.0, 1.0),
+            })
+            .collect();
+
+        let insight = set_insight(&reps).unwrap();
+        assert_eq!(insight.breakdown_after, Some(8));
+        assert_eq!(
+            insight.message,
+            "Form broke down after rep 8, consider a lighter load"
+        );
+    }
+
+    #[test]
+    fn test_one_bad_rep() {
+        let mut reps = vec![rep("Acceptable", 85.0, 1.0); 8];
+        reps[7].class = "Knee Valgus".into();
+
+        assert!(set_insight(&reps).is_none());
+    }
+
+    #[test]
+    fn test_trends() {
+        let reps: Vec<_> = (0..8)
+            .map(|i| rep("Acceptable", 80.0 + 3.0 * i as f64, 1.0 + 0.1 * i as f64))
+            .collect();
+
+        let insight = set_insight(&reps).unwrap();
+        assert_eq!(insight.breakdown_after, None);
+        assert!((insight.depth_loss.unwrap() - 21.0).abs() < 1e-9);
+        assert!((insight.slowdown.unwrap() - 0.7).abs() < 1e-9);
+        assert!(insight.message.starts_with("Reps got 21° shallower"));
+    }
+}
@@ -0,0 +1,206 @@
+use anyhow::{bail, ensure, Context};
+use serde::{Deserialize, Serialize};
+
+use super::Landmark;
+
+// identifies the blob format, bump the version if it changes
+const MAGIC: &[u8; 4] = b"LMKS";
+const VERSION: u8 = 1;
+// values are stored as multiples of this, which is well under a pixel
+const SCALE: f64 = 10_000.0;
+
+/// Landmarks of every frame of a video, as reported by the analyzer.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct LandmarkSeries {
+    /// Name of each joint, in the order they appear in a frame.
+    pub names: Vec<String>,
+    /// `None` for frames where no pose was found.
+    pub frames: Vec<Option<Vec<Landmark>>>,
+}
+
+// frames barely move from one to the next, so each value is stored as the
+// zigzag varint of how much it changed since the last frame with a pose
+pub fn encode(series: &LandmarkSeries) -> anyhow::Result<Vec<u8>> {
+    let mut buf = Vec::new();
+    buf.extend_from_slice(MAGIC);
+    buf.push(VERSION);
+
+    write_varint(&mut buf, series.names.len() as u64);
+    for name in &series.names {
+        write_varint(&mut buf, name.len() as u64);
+        buf.extend_from_slice(name.as_bytes());
+    }
+
+    write_varint(&mut buf, series.frames.len() as u64);
+    let mut prev = vec![0i64; series.names.len() * 3];
+    for (i, frame) in series.frames.iter().enumerate() {
+        let Some(landmarks) = frame else {
+            buf.push(0);
+            continue;
+        };
+        ensure!(
+            landmarks.len() == series.names.len(),
+            "Frame {i} has {} landmarks, expected {}",
+            landmarks.len(),
+            series.names.len()
+        );
+        buf.push(1);
+
+        let values = landmarks
+            .iter()
+            .flat_map(|l| [l.x, l.y, l.visibility])
+            .map(|v| (v * SCALE).round() as i64);
+        for (prev, value) in prev.iter_mut().zip(values) {
+            write_varint(&mut buf, zigzag(value - *prev));
+            *prev = value;
+        }
+    }
+
+    Ok(buf)
+}
+
+pub fn decode(buf: &[u8]) -> anyhow::Result<LandmarkSeries> {
+    let mut reader = Reader { buf };
+
+    ensure!(reader.take(MAGIC.len())? == MAGIC, "Not a landmark blob");
+    let version = reader.byte()?;
+    ensure!(
+        version == VERSION,
+        "Unsupported landmark blob version {version}"
+    );
+
+    let name_count = reader.varint()? as usize;
+    let names = (0..name_count)
+        .map(|_| {
+            let len = reader.varint()? as usize;
+            Ok(String::from_utf8(reader.take(len)?.to_vec())?)
+        })
+        .collect::<anyhow::Result<Vec<_>>>()?;
+
+    let frame_count = reader.varint()? as usize;
+    // don't trust the count with the allocation, each frame takes at least a byte
+    let mut frames = Vec::with_capacity(frame_count.min(reader.buf.len()));
+    let mut prev = vec![0i64; name_count * 3];
+    for _ in 0..frame_count {
+        match reader.byte()? {
+            0 => frames.push(None),
+            1 => {
+                for prev in prev.iter_mut() {
+                    *prev += unzigzag(reader.varint()?);
+                }
+                let landmarks = prev
+                    .chunks_exact(3)
+                    .map(|v| Landmark {
+                        x: v[0] as f64 / SCALE,
+                        y: v[1] as f64 / SCALE,
+                        visibility: v[2] as f64 / SCALE,
+                    })
+                    .collect();
+                frames.push(Some(landmarks));
+            }
+            b => bail!("Invalid frame marker {b}"),
+        }
+    }
+
+    Ok(LandmarkSeries { names, frames })
+}
+
+fn zigzag(v: i64) -> u64 {
+    ((v << 1) ^ (v >> 63)) as u64
+}
+
+fn unzigzag(v: u64) -> i64 {
+    (v >> 1) as i64 ^ -((v & 1) as i64)
+}
+
+fn write_varint(buf: &mut Vec<u8>, mut v: u64) {
+    while v >= 0x80 {
+        buf.push(v as u8 | 0x80);
+        v >>= 7;
+    }
+    buf.push(v as u8);
+}
+
+struct Reader<'a> {
+    buf: &'a [u8],
+}
+
+impl<'a> Reader<'a> {
+    fn take(&mut self, len: usize) -> anyhow::Result<&'a [u8]> {
+        ensure!(len <= self.buf.len(), "Landmark blob ended early");
+        let (taken, rest) = self.buf.split_at(len);
+        self.buf = rest;
+        Ok(taken)
+    }
+
+    fn byte(&mut self) -> anyhow::Result<u8> {
+        Ok(self.take(1)?[0])
+    }
+
+    fn varint(&mut self) -> anyhow::Result<u64> {
+        let mut v = 0;
+        for shift in (0..64).step_by(7) {
+            let b = self.byte()?;
+            v |= ((b & 0x7f) as u64) << shift;
+            if b & 0x80 == 0 {
+                return Ok(v);
+            }
+        }
+
+        None.context("Varint is too long")
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_round_trip() -> anyhow::Result<()> {
+        let lm = |x, y, visibility| Landmark { x, y, visibility };
+        let series = LandmarkSeries {
+            names: vec!["left_knee".into(), "right_knee".into()],
+            frames: vec![
+                Some(vec![lm(0.4, 0.7, 0.99), lm(0.6, 0.7, 0.98)]),
+                None,
+                Some(vec![lm(0.41, 0.72, 0.97), lm(-0.05, 1.2, 0.5)]),
+            ],
+        };
+
+        let decoded = decode(&encode(&series)?)?;
+        assert_eq!(decoded.names, series.names);
+        assert_eq!(decoded.frames.len(), series.frames.len());
+        for (a, b) in decoded.frames.iter().zip(&series.frames) {
+            match (a, b) {
+                (None, None) => (),
+                (Some(a), Some(b)) => {
+                    for (a, b) in a.iter().zip(b) {
+                        assert!((a.x - b.x).abs() <= 0.5 / SCALE);
+                        assert!((a.y - b.y).abs() <= 0.5 / SCALE);
+                        assert!((a.visibility - b.visibility).abs() <= 0.5 / SCALE);
+                    }
+                }
+                _ => panic!("Frames don't match: {a:?} {b:?}"),
+            }
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_truncated() -> anyhow::Result<()> {
+        let series = LandmarkSeries {
+            names: vec!["nose".into()],
+            frames: vec![Some(vec![Landmark {
+                x: 0.5,
+                y: 0.5,
+                visibility: 1.0,
+            }])],
+        };
+
+        let buf = encode(&series)?;
+        assert!(decode(&buf[..buf.len() - 1]).is_err());
+
+        Ok(())
+    }
+}
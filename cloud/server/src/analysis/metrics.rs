@@ -0,0 +1,207 @@
+use common_types::{Feedback, RepMetrics, WorkoutMetrics, WorkoutType};
+
+use super::{calculate_angle, landmarks::LandmarkSeries, Landmark};
+
+// indices into a frame of the joints the metrics need, left then right
+pub(super) struct Joints {
+    pub(super) shoulder: [usize; 2],
+    pub(super) hip: [usize; 2],
+    pub(super) knee: [usize; 2],
+    pub(super) ankle: [usize; 2],
+}
+
+impl Joints {
+    pub(super) fn find(names: &[String]) -> Option<Self> {
+        let find = |joint: &str| -> Option<[usize; 2]> {
+            let index = |side: &str| {
+                let name = format!("{side}_{joint}");
+                names.iter().position(|n| *n == name)
+            };
+            Some([index("left")?, index("right")?])
+        };
+
+        Some(Self {
+            shoulder: find("shoulder")?,
+            hip: find("hip")?,
+            knee: find("knee")?,
+            ankle: find("ankle")?,
+        })
+    }
+
+    pub(super) fn knee_angles(&self, frame: &[Landmark]) -> [f64; 2] {
+        [0, 1].map(|side| {
+            calculate_angle(
+                frame[self.hip[side]].point(),
+                frame[self.knee[side]].point(),
+                frame[self.ankle[side]].point(),
+            )
+        })
+    }
+
+    pub(super) fn hip_angles(&self, frame: &[Landmark]) -> [f64; 2] {
+        [0, 1].map(|side| {
+            calculate_angle(
+                frame[self.shoulder[side]].point(),
+                frame[self.hip[side]].point(),
+                frame[self.knee[side]].point(),
+            )
+        })
+    }
+}
+
+/// Works out the metrics of every segmented rep from the landmarks, returning
+/// their averages over the workout.
+///
+/// Only squats are measured for now, since the metrics are all about the knees.
+pub fn add_metrics(
+    reps: &mut [Feedback],
+    series: &LandmarkSeries,
+    workout_type: WorkoutType,
+    fps: f64,
+) -> Option<WorkoutMetrics> {
+    if workout_type != WorkoutType::Squat {
+        return None;
+    }
+    let joints = Joints::find(&series.names)?;
+
+    for rep in reps.iter_mut() {
+        rep.metrics = rep_metrics(rep, series, &joints, fps);
+    }
+
+    let metrics: Vec<_> = reps.iter().filter_map(|rep| rep.metrics).collect();
+    workout_metrics(&metrics)
+}
+
+fn rep_metrics(
+    rep: &Feedback,
+    series: &LandmarkSeries,
+    joints: &Joints,
+    fps: f64,
+) -> Option<RepMetrics> {
+    let (start, bottom, end) = (rep.start_frame?, rep.bottom_frame?, rep.end_frame?);
+    if !(start <= bottom && bottom <= end) {
+        return None;
+    }
+
+    // frames without a pose are just skipped
+    let knee_angles: Vec<_> = series
+        .frames
+        .get(start as usize..=end as usize)?
+        .iter()
+        .flatten()
+        .map(|frame| joints.knee_angles(frame))
+        .collect();
+    if knee_angles.is_empty() {
+        return None;
+    }
+
+    let min_knee_angle = knee_angles
+        .iter()
+        .flatten()
+        .copied()
+        .fold(f64::INFINITY, f64::min);
+    let asymmetry = knee_angles
+        .iter()
+        .map(|[left, right]| (left - right).abs())
+        .sum::<f64>()
+        / knee_angles.len() as f64;
+    let hip_angle_at_bottom = series.frames[bottom as usize].as_ref().map(|frame| {
+        let [left, right] = joints.hip_angles(frame);
+        (left + right) / 2.0
+    });
+
+    Some(RepMetrics {
+        eccentric_secs: (bottom - start) as f64 / fps,
+        concentric_secs: (end - bottom) as f64 / fps,
+        min_knee_angle,
+        hip_angle_at_bottom,
+        asymmetry,
+    })
+}
+
+fn workout_metrics(reps: &[RepMetrics]) -> Option<WorkoutMetrics> {
+    if reps.is_empty() {
+        return None;
+    }
+
+    let mean = |f: fn(&RepMetrics) -> f64| reps.iter().map(f).sum::<f64>() / reps.len() as f64;
+    let hip_angles: Vec<_> = reps.iter().filter_map(|r| r.hip_angle_at_bottom).collect();
+
+    Some(WorkoutMetrics {
+        rep_count: reps.len() as u32,
+        mean_eccentric_secs: mean(|r| r.eccentric_secs),
+        mean_concentric_secs: mean(|r| r.concentric_secs),
+        mean_min_knee_angle: mean(|r| r.min_knee_angle),
+        deepest_knee_angle: reps
+            .iter()
+            .map(|r| r.min_knee_angle)
+            .fold(f64::INFINITY, f64::min),
+        mean_hip_angle_at_bottom: (!hip_angles.is_empty())
+            .then(|| hip_angles.iter().sum::<f64>() / hip_angles.len() as f64),
+        mean_asymmetry: mean(|r| r.asymmetry),
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    // hips at y = 0.5 and ankles at y = 0.9, with the knees pushed forward by `bend`
+    fn frame(bend: [f64; 2]) -> Vec<Landmark> {
+        let lm = |x, y| Landmark {
+            x,
+            y,
+            visibility: 1.0,
+        };
+        [0, 1]
+            .into_iter()
+            .flat_map(|side| {
+                let x = 0.4 + side as f64 * 0.2;
+                [lm(x, 0.2), lm(x, 0.5), lm(x + bend[side], 0.7), lm(x, 0.9)]
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_rep_metrics() {
+        let names = ["shoulder", "hip", "knee", "ankle"];
+        let series = LandmarkSeries {
+            names: ["left", "right"]
+                .iter()
+                .flat_map(|side| names.map(|joint| format!("{side}_{joint}")))
+                .collect(),
+            frames: vec![
+                Some(frame([0.0, 0.0])),
+                Some(frame([0.1, 0.1])),
+                None,
+                Some(frame([0.2, 0.1])),
+                Some(frame([0.1, 0.1])),
+                Some(frame([0.0, 0.0])),
+            ],
+        };
+        let mut reps = [Feedback {
+            class: "Acceptable".into(),
+            correction: "Normal squat".into(),
+            confidence: None,
+            start_frame: Some(0),
+            bottom_frame: Some(3),
+            end_frame: Some(5),
+            metrics: None,
+            form_score: None,
+            worst_phase: None,
+        }];
+
+        let workout = add_metrics(&mut reps, &series, WorkoutType::Squat, 30.0).unwrap();
+        let rep = reps[0].metrics.unwrap();
+
+        assert_eq!(rep.eccentric_secs, 0.1);
+        assert!((rep.concentric_secs - 2.0 / 30.0).abs() < 1e-9);
+        // knee 0.2 in front of a 0.2 tall shin and thigh is 90 degrees
+        assert!((rep.min_knee_angle - 90.0).abs() < 1e-9);
+        assert!(rep.asymmetry > 0.0);
+        assert_eq!(workout.rep_count, 1);
+        assert_eq!(workout.deepest_knee_angle, rep.min_knee_angle);
+
+        assert!(add_metrics(&mut reps, &series, WorkoutType::Pushup, 30.0).is_none());
+    }
+}
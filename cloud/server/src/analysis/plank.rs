@@ -0,0 +1,172 @@
+use common_types::{HoldFault, HoldFaultKind, HoldFeedback};
+
+use super::{calculate_angle, landmarks::LandmarkSeries, metrics::Joints, Landmark};
+
+// landmarks less visible than this are mostly guesses
+const MIN_VISIBILITY: f64 = 0.5;
+// how far the body can be from horizontal while still in position, in degrees
+const MAX_TILT: f64 = 35.0;
+// how far the hips can bend from a straight line before it's a fault, in degrees
+const ALIGNMENT_TOLERANCE: f64 = 15.0;
+// faults shorter than this are put down to noise
+const MIN_FAULT_SECS: f64 = 0.5;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Pose {
+    OutOfPosition,
+    Aligned,
+    Fault(HoldFaultKind),
+}
+
+fn frame_pose(joints: &Joints, frame: &[Landmark]) -> Pose {
+    // only the side facing the camera can be trusted
+    let visibility = |side: usize| {
+        frame[joints.shoulder[side]].visibility
+            + frame[joints.hip[side]].visibility
+            + frame[joints.ankle[side]].visibility
+    };
+    let side = if visibility(0) >= visibility(1) { 0 } else { 1 };
+    let [shoulder, hip, ankle] =
+        [joints.shoulder, joints.hip, joints.ankle].map(|joint| frame[joint[side]]);
+
+    if [shoulder, hip, ankle]
+        .iter()
+        .any(|landmark| landmark.visibility < MIN_VISIBILITY)
+    {
+        return Pose::OutOfPosition;
+    }
+
+    let slope = (ankle.y - shoulder.y)
+        .atan2(ankle.x - shoulder.x)
+        .to_degrees()
+        .abs();
+    if slope.min(180.0 - slope) > MAX_TILT {
+        return Pose::OutOfPosition;
+    }
+
+    if calculate_angle(shoulder.point(), hip.point(), ankle.point()) >= 180.0 - ALIGNMENT_TOLERANCE
+    {
+        return Pose::Aligned;
+    }
+
+    // y grows downwards, so sagging hips are further down than the line at that point
+    let t = (hip.x - shoulder.x) / (ankle.x - shoulder.x);
+    let line_y = shoulder.y + t * (ankle.y - shoulder.y);
+    if hip.y > line_y {
+        Pose::Fault(HoldFaultKind::HipsSagged)
+    } else {
+        Pose::Fault(HoldFaultKind::HipsPiked)
+    }
+}
+
+/// Works out how long the plank was held and how well, from a side view.
+pub fn analyze(series: &LandmarkSeries, fps: f64) -> Option<HoldFeedback> {
+    let joints = Joints::find(&series.names)?;
+    let poses: Vec<_> = series
+        .frames
+        .iter()
+        .map(|frame| match frame {
+            Some(frame) => frame_pose(&joints, frame),
+            None => Pose::OutOfPosition,
+        })
+        .collect();
+
+    // getting into position and back up again isn't part of the hold
+    let first = poses.iter().position(|&pose| pose != Pose::OutOfPosition)?;
+    let last = poses
+        .iter()
+        .rposition(|&pose| pose != Pose::OutOfPosition)?;
+    let held = &poses[first..=last];
+
+    let aligned = held.iter().filter(|&&pose| pose == Pose::Aligned).count();
+
+    let mut faults = Vec::new();
+    let mut start = 0;
+    for (i, pose) in held.iter().enumerate() {
+        if held.get(i + 1) == Some(pose) {
+            continue;
+        }
+        if let Pose::Fault(kind) = *pose {
+            if (i + 1 - start) as f64 >= MIN_FAULT_SECS * fps {
+                faults.push(HoldFault {
+                    kind,
+                    start_frame: (first + start) as u32,
+                    end_frame: (first + i) as u32,
+                });
+            }
+        }
+        start = i + 1;
+    }
+
+    Some(HoldFeedback {
+        hold_secs: held.len() as f64 / fps,
+        aligned_secs: aligned as f64 / fps,
+        faults,
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    // side view with the shoulders at x = 0.2 and the ankles at x = 0.8,
+    // where `hip_y` is how far the hips are below the line between them
+    fn frame(lying: bool, hip_y: f64) -> Option<Vec<Landmark>> {
+        let lm = |x, y| Landmark {
+            x,
+            y,
+            visibility: 0.9,
+        };
+        let joints = if lying {
+            [
+                lm(0.2, 0.5),
+                lm(0.45, 0.5 + hip_y),
+                lm(0.65, 0.5),
+                lm(0.8, 0.5),
+            ]
+        } else {
+            [lm(0.5, 0.2), lm(0.5, 0.5), lm(0.5, 0.7), lm(0.5, 0.9)]
+        };
+
+        // both sides are in the same place from the side
+        Some(joints.iter().flat_map(|&joint| [joint, joint]).collect())
+    }
+
+    #[test]
+    fn test_hold() {
+        let names = ["shoulder", "hip", "knee", "ankle"];
+        let frames = [
+            (10, frame(false, 0.0)),
+            (30, frame(true, 0.0)),
+            (20, frame(true, 0.1)),
+            (30, frame(true, 0.0)),
+            (5, frame(true, -0.1)),
+            (10, None),
+            (10, frame(true, 0.01)),
+            (20, frame(false, 0.0)),
+        ];
+        let series = LandmarkSeries {
+            names: names
+                .iter()
+                .flat_map(|joint| [format!("left_{joint}"), format!("right_{joint}")])
+                .collect(),
+            frames: frames
+                .into_iter()
+                .flat_map(|(count, frame)| vec![frame; count])
+                .collect(),
+        };
+
+        let hold = analyze(&series, 30.0).unwrap();
+        assert_eq!(hold.hold_secs, 105.0 / 30.0);
+        assert_eq!(hold.aligned_secs, 70.0 / 30.0);
+        // the piked hips were too short to count
+        assert_eq!(
+            hold.faults,
+            [HoldFault {
+                kind: HoldFaultKind::HipsSagged,
+                start_frame: 40,
+                end_frame: 59,
+            }]
+        );
+    }
+}
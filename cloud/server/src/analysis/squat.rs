@@ -0,0 +1,368 @@
+use common_types::Feedback;
+use serde::{Deserialize, Serialize};
+
+use super::{calculate_angle, calculate_distance, Landmark};
+
+/// Landmarks used for a squat, in the order the predictor sends them.
+pub const LANDMARK_COUNT: usize = 12;
+/// Knee, hip and ankle angles on the left, then the same on the right.
+pub const ANGLE_COUNT: usize = 6;
+/// Angles, hip width, then distances relative to the hip width.
+pub const FEATURE_COUNT: usize = 21;
+pub const CLASS_COUNT: usize = 7;
+
+const LEFT_SHOULDER: usize = 0;
+const RIGHT_SHOULDER: usize = 1;
+const LEFT_HIP: usize = 2;
+const RIGHT_HIP: usize = 3;
+const LEFT_KNEE: usize = 4;
+const RIGHT_KNEE: usize = 5;
+const LEFT_ANKLE: usize = 6;
+const RIGHT_ANKLE: usize = 7;
+const LEFT_HEEL: usize = 8;
+const RIGHT_HEEL: usize = 9;
+const LEFT_FOOT_INDEX: usize = 10;
+const RIGHT_FOOT_INDEX: usize = 11;
+
+pub type Landmarks = [Landmark; LANDMARK_COUNT];
+pub type Features = [f64; FEATURE_COUNT];
+pub type Probabilities = [f64; CLASS_COUNT];
+
+/// What the predictor reports for a rep, taken at its lowest knee angle.
+#[derive(Deserialize, Debug)]
+pub struct RepPrediction {
+    pub landmarks: Landmarks,
+    pub probabilities: Probabilities,
+    #[serde(default)]
+    pub start_frame: Option<u32>,
+    #[serde(default)]
+    pub bottom_frame: Option<u32>,
+    #[serde(default)]
+    pub end_frame: Option<u32>,
+}
+
+/// Classes in the order the model predicts them.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SquatClass {
+    Acceptable,
+    AnteriorKnee,
+    BentOver,
+    KneeValgus,
+    KneeVarus,
+    HalfSquat,
+    Other,
+}
+
+impl SquatClass {
+    const ALL: [SquatClass; CLASS_COUNT] = [
+        Self::Acceptable,
+        Self::AnteriorKnee,
+        Self::BentOver,
+        Self::KneeValgus,
+        Self::KneeVarus,
+        Self::HalfSquat,
+        Self::Other,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Acceptable => "Acceptable",
+            Self::AnteriorKnee => "Anterior Knee",
+            Self::BentOver => "Bent Over",
+            Self::KneeValgus => "Knee Valgus",
+            Self::KneeVarus => "Knee Varus",
+            Self::HalfSquat => "Half Squat",
+            Self::Other => "Other",
+        }
+    }
+
+    pub fn description(self) -> &'static str {
+        match self {
+            Self::Acceptable => "Normal squat",
+            Self::AnteriorKnee => "Knee ahead of toes during exercise",
+            Self::BentOver => "Excessive flexing of hip and torso",
+            Self::KneeValgus => "Both knees pointing inside during exercise",
+            Self::KneeVarus => "Both knees pointing outside during exercise",
+            Self::HalfSquat => "Insufficient squatting depth",
+            Self::Other => "Probably standing most of the time",
+        }
+    }
+}
+
+/// Thresholds for scoring, which can be tuned without retraining the model.
+#[derive(Deserialize, Debug, Clone, PartialEq)]
+#[serde(default)]
+pub struct ScoringConfig {
+    /// Angles of a perfect squat at the bottom, in the order of `derive_features`.
+    pub perfect_angles: [f64; ANGLE_COUNT],
+    /// How many degrees an angle can be off while still counting as perfect.
+    pub angle_tolerance: f64,
+    /// How much closeness to the perfect angles is divided by before it is
+    /// taken from the error classes and given to `Acceptable`.
+    pub acceptable_divisor: f64,
+}
+
+impl Default for ScoringConfig {
+    fn default() -> Self {
+        Self {
+            // from ml/assets/_squat_perfect.mp4, using
+            // https://www.raynersmale.com/blog/2014/1/31/optimising-your-squat
+            perfect_angles: [
+                84.87369318857579,
+                83.45166654986053,
+                153.37894731195783,
+                85.30513286618259,
+                83.7632826648259,
+                179.36188848969115,
+            ],
+            angle_tolerance: 7.0,
+            acceptable_divisor: 1.5,
+        }
+    }
+}
+
+impl ScoringConfig {
+    /// Reads the JSON file at `SQUAT_SCORING_CONFIG` if it is set, where missing
+    /// fields keep their defaults.
+    pub fn from_env() -> Self {
+        match std::env::var("SQUAT_SCORING_CONFIG") {
+            Ok(path) => {
+                let config = std::fs::read_to_string(&path)
+                    .unwrap_or_else(|e| panic!("Failed to read {path}: {e}"));
+                serde_json::from_str(&config)
+                    .unwrap_or_else(|e| panic!("{path} is not a valid scoring config: {e}"))
+            }
+            Err(_) => Self::default(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct RepScore {
+    pub class: SquatClass,
+    /// Probability of `class` after scoring, out of 100.
+    pub confidence: f64,
+}
+
+impl From<RepScore> for Feedback {
+    fn from(RepScore { class, confidence }: RepScore) -> Self {
+        Feedback {
+            class: class.name().into(),
+            correction: class.description().into(),
+            confidence: Some(confidence),
+            start_frame: None,
+            bottom_frame: None,
+            end_frame: None,
+            metrics: None,
+            form_score: None,
+            worst_phase: None,
+        }
+    }
+}
+
+pub fn derive_features(f: &Landmarks) -> Features {
+    let angle =
+        |a: usize, b: usize, c: usize| calculate_angle(f[a].point(), f[b].point(), f[c].point());
+    let distance = |a: usize, b: usize| calculate_distance(f[a].point(), f[b].point());
+
+    // everything else is relative to this, so it doesn't matter how far away the person is
+    let hip_width = distance(LEFT_HIP, RIGHT_HIP);
+    let ratio = |a: usize, b: usize| distance(a, b) / hip_width;
+
+    [
+        angle(LEFT_HIP, LEFT_KNEE, LEFT_ANKLE),
+        angle(LEFT_SHOULDER, LEFT_HIP, LEFT_KNEE),
+        angle(LEFT_KNEE, LEFT_ANKLE, LEFT_FOOT_INDEX),
+        angle(RIGHT_HIP, RIGHT_KNEE, RIGHT_ANKLE),
+        angle(RIGHT_SHOULDER, RIGHT_HIP, RIGHT_KNEE),
+        angle(RIGHT_KNEE, RIGHT_ANKLE, RIGHT_FOOT_INDEX),
+        hip_width,
+        ratio(LEFT_SHOULDER, LEFT_HIP),
+        ratio(RIGHT_SHOULDER, RIGHT_HIP),
+        ratio(LEFT_SHOULDER, RIGHT_SHOULDER),
+        ratio(LEFT_HIP, LEFT_KNEE),
+        ratio(RIGHT_HIP, RIGHT_KNEE),
+        ratio(LEFT_KNEE, LEFT_ANKLE),
+        ratio(RIGHT_KNEE, RIGHT_ANKLE),
+        ratio(LEFT_ANKLE, LEFT_HEEL),
+        ratio(LEFT_HEEL, LEFT_FOOT_INDEX),
+        ratio(LEFT_FOOT_INDEX, LEFT_ANKLE),
+        ratio(RIGHT_ANKLE, RIGHT_HEEL),
+        ratio(RIGHT_HEEL, RIGHT_FOOT_INDEX),
+        ratio(RIGHT_FOOT_INDEX, RIGHT_ANKLE),
+        ratio(LEFT_KNEE, RIGHT_KNEE),
+    ]
+}
+
+// how close the angles are to a perfect squat, from 1 when all are within tolerance
+fn closeness(angles: &[f64], config: &ScoringConfig) -> f64 {
+    let total: f64 = angles
+        .iter()
+        .zip(config.perfect_angles)
+        .map(|(&angle, perfect)| {
+            let diff = (perfect - angle).abs();
+            if diff <= config.angle_tolerance {
+                1.0
+            } else {
+                1.0 - diff / (perfect - config.angle_tolerance)
+            }
+        })
+        .sum();
+
+    total / ANGLE_COUNT as f64
+}
+
+pub fn score_features(
+    features: &Features,
+    probabilities: &Probabilities,
+    config: &ScoringConfig,
+) -> RepScore {
+    let closeness = closeness(&features[..ANGLE_COUNT], config) / config.acceptable_divisor;
+
+    // the closer the form is, the more of the form error classes goes to acceptable
+    // half squat and other aren't about form, so they are left alone
+    let mut probabilities = *probabilities;
+    for class in [
+        SquatClass::AnteriorKnee,
+        SquatClass::BentOver,
+        SquatClass::KneeValgus,
+        SquatClass::KneeVarus,
+    ] {
+        let taken = closeness * probabilities[class as usize];
+        probabilities[class as usize] -= taken;
+        probabilities[SquatClass::Acceptable as usize] += taken;
+    }
+
+    // first one wins ties, same as argmax
+    let (class, probability) = SquatClass::ALL.into_iter().zip(probabilities).fold(
+        (SquatClass::Acceptable, f64::NEG_INFINITY),
+        |best, next| {
+            if next.1 > best.1 {
+                next
+            } else {
+                best
+            }
+        },
+    );
+
+    RepScore {
+        class,
+        confidence: probability * 100.0,
+    }
+}
+
+pub fn score(prediction: &RepPrediction, config: &ScoringConfig) -> RepScore {
+    score_features(
+        &derive_features(&prediction.landmarks),
+        &prediction.probabilities,
+        config,
+    )
+}
+
+pub fn rep_feedback(prediction: &RepPrediction, config: &ScoringConfig) -> Feedback {
+    Feedback {
+        start_frame: prediction.start_frame,
+        bottom_frame: prediction.bottom_frame,
+        end_frame: prediction.end_frame,
+        ..score(prediction, config).into()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn features_with_angles(angles: [f64; ANGLE_COUNT]) -> Features {
+        let mut features = [1.0; FEATURE_COUNT];
+        features[..ANGLE_COUNT].copy_from_slice(&angles);
+        features
+    }
+
+    fn assert_close(a: f64, b: f64) {
+        assert!((a - b).abs() < 1e-9, "{a} != {b}");
+    }
+
+    #[test]
+    fn test_angle() {
+        assert_close(calculate_angle((1.0, 0.0), (0.0, 0.0), (0.0, 1.0)), 90.0);
+        assert_close(calculate_angle((-1.0, 0.0), (0.0, 0.0), (1.0, 0.0)), 180.0);
+        // always the inner angle
+        assert_close(calculate_angle((0.0, 1.0), (0.0, 0.0), (1.0, -1.0)), 135.0);
+    }
+
+    #[test]
+    fn test_derive_features() {
+        let lm = |x, y| Landmark {
+            x,
+            y,
+            visibility: 1.0,
+        };
+        // standing straight with feet pointing forward, hips 0.2 apart
+        let landmarks = [
+            lm(0.4, 0.2),
+            lm(0.6, 0.2),
+            lm(0.4, 0.5),
+            lm(0.6, 0.5),
+            lm(0.4, 0.7),
+            lm(0.6, 0.7),
+            lm(0.4, 0.9),
+            lm(0.6, 0.9),
+            lm(0.38, 0.9),
+            lm(0.58, 0.9),
+            lm(0.45, 0.9),
+            lm(0.65, 0.9),
+        ];
+
+        let features = derive_features(&landmarks);
+        assert_close(features[0], 180.0);
+        assert_close(features[1], 180.0);
+        assert_close(features[2], 90.0);
+        assert_close(features[6], 0.2);
+        // shoulder to hip is 0.3, so 1.5 hip widths
+        assert_close(features[7], 1.5);
+        assert_close(features[20], 1.0);
+    }
+
+    #[test]
+    fn test_perfect_form_is_acceptable() {
+        let config = ScoringConfig::default();
+        let features = features_with_angles(config.perfect_angles);
+        let probabilities = [0.3, 0.0, 0.0, 0.5, 0.0, 0.1, 0.1];
+
+        // two thirds of knee valgus goes to acceptable
+        let score = score_features(&features, &probabilities, &config);
+        assert_eq!(score.class, SquatClass::Acceptable);
+        assert_close(score.confidence, (0.3 + 0.5 * 2.0 / 3.0) * 100.0);
+    }
+
+    #[test]
+    fn test_bad_form_keeps_prediction() {
+        let config = ScoringConfig::default();
+        let features = features_with_angles([150.0, 150.0, 150.0, 150.0, 150.0, 150.0]);
+        let probabilities = [0.1, 0.0, 0.0, 0.7, 0.0, 0.1, 0.1];
+
+        let score = score_features(&features, &probabilities, &config);
+        assert_eq!(score.class, SquatClass::KneeValgus);
+    }
+
+    #[test]
+    fn test_depth_is_not_blended() {
+        let config = ScoringConfig::default();
+        let features = features_with_angles(config.perfect_angles);
+        let probabilities = [0.2, 0.0, 0.0, 0.0, 0.0, 0.8, 0.0];
+
+        let score = score_features(&features, &probabilities, &config);
+        assert_eq!(score.class, SquatClass::HalfSquat);
+        assert_close(score.confidence, 80.0);
+    }
+
+    #[test]
+    fn test_partial_config() {
+        let config: ScoringConfig = serde_json::from_str(r#"{ "angle_tolerance": 10 }"#).unwrap();
+        assert_close(config.angle_tolerance, 10.0);
+        assert_eq!(
+            config.perfect_angles,
+            ScoringConfig::default().perfect_angles
+        );
+    }
+}
@@ -0,0 +1,244 @@
+use common_types::{Feedback, RepPhase};
+use serde::{Deserialize, Serialize};
+
+use super::{landmarks::LandmarkSeries, metrics::Joints};
+
+// average deviation in degrees at which a rep scores 0
+const MAX_DEVIATION: f64 = 45.0;
+// how much of the reference either side of its deepest frame counts as the bottom
+const BOTTOM_WINDOW: f64 = 0.1;
+
+/// Joint angles of a single frame in degrees, left then right.
+// kept as a struct rather than a flat array since firestore can't store nested arrays
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+pub struct Angles {
+    pub knees: [f64; 2],
+    pub hips: [f64; 2],
+}
+
+impl Angles {
+    fn values(&self) -> impl Iterator<Item = f64> {
+        self.knees.into_iter().chain(self.hips)
+    }
+
+    // average difference between each angle
+    fn distance(&self, other: &Angles) -> f64 {
+        let (sum, count) = self
+            .values()
+            .zip(other.values())
+            .fold((0.0, 0), |(sum, count), (a, b)| {
+                (sum + (a - b).abs(), count + 1)
+            });
+        sum / count as f64
+    }
+}
+
+/// Joint angles over the frames of a rep.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct Trajectory {
+    pub frames: Vec<Angles>,
+}
+
+impl Trajectory {
+    /// Angles of frames `start..=end` of the series, skipping those without a pose.
+    pub fn extract(series: &LandmarkSeries, start: u32, end: u32) -> Option<Self> {
+        let joints = Joints::find(&series.names)?;
+        // uploaded series can have frames missing some of their joints
+        let complete = series
+            .frames
+            .iter()
+            .flatten()
+            .all(|frame| frame.len() == series.names.len());
+        if !complete {
+            return None;
+        }
+
+        let frames: Vec<_> = series
+            .frames
+            .get(start as usize..=end as usize)?
+            .iter()
+            .flatten()
+            .map(|frame| Angles {
+                knees: joints.knee_angles(frame),
+                hips: joints.hip_angles(frame),
+            })
+            .collect();
+
+        (!frames.is_empty()).then_some(Self { frames })
+    }
+
+    // the phase that a frame of this trajectory falls in
+    fn phase(&self, frame: usize) -> RepPhase {
+        let bottom = self
+            .frames
+            .iter()
+            .map(|angles| angles.knees[0] + angles.knees[1])
+            .enumerate()
+            .min_by(|(_, a), (_, b)| a.total_cmp(b))
+            .map_or(0, |(i, _)| i);
+        let window = (self.frames.len() as f64 * BOTTOM_WINDOW).ceil() as usize;
+
+        if frame + window < bottom {
+            RepPhase::Descent
+        } else if frame > bottom + window {
+            RepPhase::Ascent
+        } else {
+            RepPhase::Bottom
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Comparison {
+    /// Out of 100, where 100 follows the reference exactly.
+    pub score: f64,
+    pub worst_phase: RepPhase,
+}
+
+/// Compares a rep against a reference rep, regardless of how fast either was done.
+pub fn compare(rep: &Trajectory, reference: &Trajectory) -> Comparison {
+    let (mean, worst) = align(&rep.frames, &reference.frames);
+
+    Comparison {
+        score: (100.0 * (1.0 - mean / MAX_DEVIATION)).clamp(0.0, 100.0),
+        worst_phase: reference.phase(worst),
+    }
+}
+
+// dynamic time warping, returning the average distance along the cheapest
+// alignment and the frame of `reference` where the distance was greatest
+fn align(rep: &[Angles], reference: &[Angles]) -> (f64, usize) {
+    let (n, m) = (rep.len(), reference.len());
+    if n == 0 || m == 0 {
+        return (f64::INFINITY, 0);
+    }
+
+    // cost[i][j] is the cheapest way to line up the first i frames with the first j
+    let mut cost = vec![vec![f64::INFINITY; m + 1]; n + 1];
+    cost[0][0] = 0.0;
+    for i in 1..=n {
+        for j in 1..=m {
+            let prev = cost[i - 1][j - 1].min(cost[i - 1][j]).min(cost[i][j - 1]);
+            cost[i][j] = rep[i - 1].distance(&reference[j - 1]) + prev;
+        }
+    }
+
+    // walk back along the cheapest path to find its length and worst step
+    let (mut i, mut j) = (n, m);
+    let mut steps = 0;
+    let mut worst = (f64::NEG_INFINITY, 0);
+    while i > 0 && j > 0 {
+        let distance = rep[i - 1].distance(&reference[j - 1]);
+        if distance > worst.0 {
+            worst = (distance, j - 1);
+        }
+        steps += 1;
+
+        let (diagonal, up, left) = (cost[i - 1][j - 1], cost[i - 1][j], cost[i][j - 1]);
+        if diagonal <= up && diagonal <= left {
+            (i, j) = (i - 1, j - 1);
+        } else if up <= left {
+            i -= 1;
+        } else {
+            j -= 1;
+        }
+    }
+
+    (cost[n][m] / steps as f64, worst.1)
+}
+
+/// Scores each segmented rep against whichever reference it matches best.
+pub fn add_form_scores(reps: &mut [Feedback], series: &LandmarkSeries, references: &[Trajectory]) {
+    for rep in reps.iter_mut() {
+        let (Some(start), Some(end)) = (rep.start_frame, rep.end_frame) else {
+            continue;
+        };
+        let Some(trajectory) = Trajectory::extract(series, start, end) else {
+            continue;
+        };
+
+        let best = references
+            .iter()
+            .map(|reference| compare(&trajectory, reference))
+            .max_by(|a, b| a.score.total_cmp(&b.score));
+        if let Some(best) = best {
+            rep.form_score = Some(best.score);
+            rep.worst_phase = Some(best.worst_phase);
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::analysis::Landmark;
+
+    // a rep that goes from standing down to `depth` and back up over `frames` frames
+    fn rep(frames: usize, depth: f64) -> Trajectory {
+        let frames = (0..frames)
+            .map(|i| {
+                let t = i as f64 / (frames - 1) as f64;
+                let knee = 170.0 - (170.0 - depth) * (t * std::f64::consts::PI).sin();
+                Angles {
+                    knees: [knee; 2],
+                    hips: [knee; 2],
+                }
+            })
+            .collect();
+        Trajectory { frames }
+    }
+
+    #[test]
+    fn test_same_rep() {
+        let reference = rep(30, 90.0);
+
+        assert_eq!(compare(&reference, &reference).score, 100.0);
+    }
+
+    #[test]
+    fn test_slower_rep() {
+        let reference = rep(30, 90.0);
+        let comparison = compare(&rep(75, 90.0), &reference);
+
+        assert!(comparison.score > 95.0, "{comparison:?}");
+    }
+
+    #[test]
+    fn test_shallow_rep() {
+        let reference = rep(30, 90.0);
+        let comparison = compare(&rep(30, 130.0), &reference);
+
+        assert!(comparison.score < 80.0, "{comparison:?}");
+        assert_eq!(comparison.worst_phase, RepPhase::Bottom);
+    }
+
+    #[test]
+    fn test_short_frame() {
+        let names = ["shoulder", "hip", "knee", "ankle"];
+        let landmark = Landmark {
+            x: 0.5,
+            y: 0.5,
+            visibility: 1.0,
+        };
+        let mut series = LandmarkSeries {
+            names: ["left", "right"]
+                .iter()
+                .flat_map(|side| names.map(|joint| format!("{side}_{joint}")))
+                .collect(),
+            frames: vec![Some(vec![landmark; 8]), None, Some(vec![landmark; 8])],
+        };
+        assert!(Trajectory::extract(&series, 0, 2).is_some());
+
+        series.frames[2] = Some(vec![landmark; 5]);
+        assert_eq!(Trajectory::extract(&series, 0, 2), None);
+    }
+
+    #[test]
+    fn test_phase() {
+        let reference = rep(31, 90.0);
+
+        assert_eq!(reference.phase(0), RepPhase::Descent);
+        assert_eq!(reference.phase(15), RepPhase::Bottom);
+        assert_eq!(reference.phase(30), RepPhase::Ascent);
+    }
+}
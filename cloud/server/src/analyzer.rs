@@ -0,0 +1,206 @@
+use std::sync::Arc;
+
+use anyhow::Context;
+use common_types::{
+    ExerciseKind, Feedback, Frame, HoldFeedback, WorkoutType, IMAGE_HEIGHT, IMAGE_WIDTH,
+};
+use image::{ImageBuffer, RgbImage};
+use rayon::prelude::{IndexedParallelIterator, IntoParallelIterator, ParallelIterator};
+use rgb565::Rgb565;
+use serde::{Deserialize, Serialize};
+use tokio::process::Command;
+
+use crate::{
+    analysis::{
+        fatigue::set_insight,
+        landmarks::LandmarkSeries,
+        metrics::add_metrics,
+        plank,
+        squat::{self, RepPrediction, ScoringConfig},
+        trajectory::add_form_scores,
+    },
+    constants::VIDEO_FPS,
+    profile::Profile,
+    reference::list_references,
+    types::state::AppState,
+    workout::WorkoutEntry,
+};
+
+const NAME_WIDTH: usize = 4;
+
+// what produced a set of feedback, as reported by the predictor
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct AnalyzerInfo {
+    pub name: String,
+    pub version: String,
+    pub model_hash: String,
+}
+
+#[derive(Deserialize, Debug)]
+pub struct Analysis {
+    pub analyzer: AnalyzerInfo,
+    #[serde(flatten)]
+    pub output: AnalyzerOutput,
+    // pose of every frame, if the analyzer tracks it
+    #[serde(default)]
+    pub landmarks: Option<LandmarkSeries>,
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(rename_all = "snake_case")]
+pub enum AnalyzerOutput {
+    // the analyzer already decided on the feedback
+    Reps(Vec<Feedback>),
+    // the analyzer only estimated the pose, the rest is scored here
+    SquatPredictions(Vec<RepPrediction>),
+    // the analyzer only tracked the pose, everything is worked out from the landmarks
+    PoseOnly {},
+}
+
+impl AnalyzerOutput {
+    pub fn into_feedback(self, scoring: &ScoringConfig) -> Vec<Feedback> {
+        match self {
+            AnalyzerOutput::Reps(reps) => reps,
+            AnalyzerOutput::SquatPredictions(predictions) => predictions
+                .iter()
+                .map(|prediction| squat::rep_feedback(prediction, scoring))
+                .collect(),
+            AnalyzerOutput::PoseOnly {} => Vec::new(),
+        }
+    }
+}
+
+type HoldAnalysis = fn(&LandmarkSeries, f64) -> Option<HoldFeedback>;
+
+// how each exercise gets analyzed
+struct AnalyzerSpec {
+    // predictor that is run on the frames
+    script: &'static str,
+    // works out the feedback of a held exercise from its landmarks
+    analyze_hold: Option<HoldAnalysis>,
+}
+
+fn analyzer_spec(workout_type: WorkoutType) -> AnalyzerSpec {
+    match workout_type {
+        WorkoutType::Squat => AnalyzerSpec {
+            script: "./.ml/squatPredictor.py",
+            analyze_hold: None,
+        },
+        WorkoutType::Pushup => AnalyzerSpec {
+            script: "./.ml/pushupPredictor.py",
+            analyze_hold: None,
+        },
+        WorkoutType::Plank => AnalyzerSpec {
+            script: "./.ml/plankPredictor.py",
+            analyze_hold: Some(plank::analyze),
+        },
+    }
+}
+
+// fills in the feedback of the entry, going further than the analyzer when there are landmarks
+pub async fn apply_analysis(
+    state: &AppState,
+    entry: &mut WorkoutEntry,
+    analysis: Analysis,
+    profile: &Profile,
+) -> anyhow::Result<()> {
+    if entry.workout_type.kind() == ExerciseKind::Duration {
+        let analyze_hold = analyzer_spec(entry.workout_type).analyze_hold;
+        entry.hold = analyze_hold
+            .zip(analysis.landmarks.as_ref())
+            .and_then(|(analyze_hold, landmarks)| analyze_hold(landmarks, VIDEO_FPS));
+        entry.analyzer = Some(analysis.analyzer);
+        return Ok(());
+    }
+
+    let mut reps = analysis
+        .output
+        .into_feedback(&profile.scoring(&state.scoring));
+
+    entry.metrics = None;
+    if let Some(landmarks) = &analysis.landmarks {
+        entry.metrics = add_metrics(&mut reps, landmarks, entry.workout_type, VIDEO_FPS);
+
+        let references = list_references(&state.db, entry.workout_type).await?;
+        add_form_scores(&mut reps, landmarks, &references);
+    }
+
+    entry.insight = set_insight(&reps);
+    entry.analyzer = Some(analysis.analyzer);
+    entry.reps = Some(reps);
+
+    Ok(())
+}
+
+// saves frames as numbered images, starting from `offset`
+pub async fn save_frames(
+    folder_path: Arc<str>,
+    frames: Vec<Frame>,
+    offset: usize,
+) -> anyhow::Result<()> {
+    // combine oneshot with rayon
+    let (send, recv) = tokio::sync::oneshot::channel();
+
+    rayon::spawn(move || {
+        let res =
+            frames
+                .into_par_iter()
+                .enumerate()
+                .try_for_each(|(i, frame)| -> anyhow::Result<()> {
+                    let img = frame_to_image(frame, IMAGE_HEIGHT as u32, IMAGE_WIDTH as u32)?;
+                    let filename = format!("{folder_path}/{j:0NAME_WIDTH$}.png", j = offset + i);
+                    img.save(&filename)
+                        .context(format!("Failed to save file: {filename}"))?;
+
+                    Ok(())
+                });
+
+        _ = send.send(res);
+    });
+
+    // acts like an async join
+    recv.await?
+}
+
+fn frame_to_image(Frame(buf): Frame, height: u32, width: u32) -> anyhow::Result<RgbImage> {
+    // need to convert little endian to rgb
+    let buf: Vec<_> = buf
+        .chunks_exact(2)
+        .flat_map(|c| Rgb565::from_rgb565_le([c[0], c[1]]).to_rgb888_components())
+        .collect();
+
+    ImageBuffer::from_vec(width, height, buf).context("Failed to create image")
+}
+
+// runs the predictor on the frames in `folder_path`, which also writes the annotated video
+pub async fn call_ml(
+    video_path: &str,
+    folder_path: &str,
+    workout_type: WorkoutType,
+    rep_angle: Option<f64>,
+) -> anyhow::Result<Analysis> {
+    let ml_path = analyzer_spec(workout_type).script;
+
+    let mut command = Command::new("python");
+    command.args([
+        ml_path,
+        &format!("{folder_path}/%0{NAME_WIDTH}d.png"),
+        video_path,
+    ]);
+    if let Some(rep_angle) = rep_angle {
+        command.arg(rep_angle.to_string());
+    }
+
+    let res = command.output().await?;
+
+    tracing::debug!(
+        "stdout from {ml_path}: {}",
+        String::from_utf8_lossy(&res.stdout)
+    );
+    tracing::debug!(
+        "stderr from {ml_path}: {}",
+        String::from_utf8_lossy(&res.stderr)
+    );
+
+    Ok(serde_json::from_slice(&res.stdout)?)
+}
@@ -0,0 +1,55 @@
+use anyhow::ensure;
+use tokio::process::Command;
+use uuid::Uuid;
+
+use crate::constants::VIDEO_PATH;
+
+// cuts frames start..=end out of an mp4 with ffmpeg, returning the new mp4
+pub async fn extract_clip(
+    video: Vec<u8>,
+    start_frame: u32,
+    end_frame: u32,
+) -> anyhow::Result<Vec<u8>> {
+    ensure!(start_frame <= end_frame, "Clip ends before it starts");
+
+    let id = Uuid::new_v4();
+    let in_path = format!("{VIDEO_PATH}/{id}.in.mp4");
+    let out_path = format!("{VIDEO_PATH}/{id}.clip.mp4");
+    tokio::fs::write(&in_path, video).await?;
+
+    // trim by frame rather than time so that it lines up with the rep exactly
+    let res = Command::new("ffmpeg")
+        .args([
+            "-y",
+            "-loglevel",
+            "error",
+            "-i",
+            &in_path,
+            "-vf",
+            &format!(
+                "trim=start_frame={start_frame}:end_frame={},setpts=PTS-STARTPTS",
+                end_frame + 1
+            ),
+            "-an",
+            "-movflags",
+            "+faststart",
+            &out_path,
+        ])
+        .output()
+        .await;
+
+    let clip = match res {
+        Ok(res) if res.status.success() => tokio::fs::read(&out_path).await.map_err(Into::into),
+        Ok(res) => Err(anyhow::anyhow!(
+            "ffmpeg failed: {}",
+            String::from_utf8_lossy(&res.stderr)
+        )),
+        Err(e) => Err(e.into()),
+    };
+
+    // clean up whether it worked or not
+    _ = tokio::fs::remove_file(&in_path).await;
+    _ = tokio::fs::remove_file(&out_path).await;
+
+    clip
+}
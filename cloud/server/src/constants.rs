@@ -1,3 +1,7 @@
+use std::time::Duration;
+
+use common_types::IMAGE_SIZE;
+
 pub const CHANNEL_SIZE: usize = 10;
 
 pub const VIDEO_PATH: &str = "./.video";
@@ -5,3 +9,26 @@ pub const VIDEO_PATH: &str = "./.video";
 pub const BUCKET_NAME: &str = "gym-tr-ai-ner.appspot.com";
 pub const USER_COLLECTION: &str = "users";
 pub const WORKOUT_COLLECTION: &str = "workouts";
+pub const REVISION_COLLECTION: &str = "revisions";
+pub const REFERENCE_COLLECTION: &str = "references";
+pub const PROFILE_COLLECTION: &str = "profiles";
+
+// the analyzer writes its videos at this rate, so frame numbers can be turned into time
+pub const VIDEO_FPS: f64 = 30.0;
+
+// raw frames get written here inside the video folder, if they are being kept
+pub const FOOTAGE_NAME: &str = "footage.raw";
+
+// defaults for the device limits, which can be overridden from the environment
+pub const DEFAULT_MAX_MESSAGE_SIZE: usize = 16 << 20; // 16 MiB
+pub const DEFAULT_MAX_BATCH_FRAMES: usize = 60;
+// 6 minutes at 30 fps
+pub const DEFAULT_MAX_VIDEO_FRAMES: usize = 30 * 6 * 60;
+// longer than the device records for, since its last frames arrive a little after it stops
+pub const DEFAULT_MAX_VIDEO_LENGTH: Duration = Duration::from_secs(6 * 60);
+pub const DEFAULT_MAX_BANDWIDTH: usize = 2 * 30 * IMAGE_SIZE; // twice what 30 fps needs
+pub const BANDWIDTH_WINDOW: Duration = Duration::from_secs(5);
+// how long before a video is stopped that the device is warned
+pub const LIMIT_WARNING: Duration = Duration::from_secs(15);
+// analysis that has been going on for longer than this is taken to have died with the server
+pub const PROCESSING_TIMEOUT: Duration = Duration::from_secs(30 * 60);
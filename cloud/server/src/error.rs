@@ -7,6 +7,26 @@ use thiserror::Error;
 pub enum AppError {
     #[error("The ID already exists")]
     DuplicateId,
+    #[error("The workout does not exist")]
+    NoSuchWorkout,
+    #[error("The workout has no stored footage")]
+    NoFootage,
+    #[error("The workout has no landmarks")]
+    NoLandmarks,
+    #[error("The rep does not exist or was not segmented")]
+    NoSuchRep,
+    #[error("The workout is still being processed")]
+    WorkoutBusy,
+    #[error("The workout failed, so has nothing to use")]
+    WorkoutFailed,
+    #[error("Missing or wrong admin token")]
+    Unauthorized,
+    #[error("The recording has no usable frames")]
+    InvalidReference,
+    #[error("Only squats can be used for calibration")]
+    NotASquat,
+    #[error("The profile has values out of range")]
+    InvalidProfile,
     #[error("An internal server error occurred: {0}")]
     InternalServerError(anyhow::Error),
 }
@@ -14,7 +34,17 @@ pub enum AppError {
 impl IntoResponse for AppError {
     fn into_response(self) -> axum::response::Response {
         let status = match self {
-            AppError::DuplicateId => StatusCode::BAD_REQUEST,
+            AppError::DuplicateId
+            | AppError::InvalidReference
+            | AppError::NotASquat
+            | AppError::InvalidProfile => StatusCode::BAD_REQUEST,
+            AppError::Unauthorized => StatusCode::UNAUTHORIZED,
+            AppError::NoSuchWorkout | AppError::NoLandmarks | AppError::NoSuchRep => {
+                StatusCode::NOT_FOUND
+            }
+            AppError::NoFootage | AppError::WorkoutBusy | AppError::WorkoutFailed => {
+                StatusCode::CONFLICT
+            }
             AppError::InternalServerError(..) => StatusCode::INTERNAL_SERVER_ERROR,
         };
 
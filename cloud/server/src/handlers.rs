@@ -1 +1,4 @@
+pub mod admin;
 pub mod connect;
+pub mod profile;
+pub mod workout;
@@ -0,0 +1,69 @@
+use std::sync::Arc;
+
+use axum::{
+    extract::State,
+    http::{header, HeaderMap, StatusCode},
+    Json,
+};
+use common_types::WorkoutType;
+use serde::Deserialize;
+
+use crate::{
+    analysis::{landmarks::LandmarkSeries, trajectory::Trajectory},
+    error::AppError,
+    reference::{self, ReferenceEntry},
+    types::state::AppState,
+};
+
+#[derive(Deserialize, Debug)]
+pub struct ReferenceUpload {
+    pub name: String,
+    #[serde(rename = "type")]
+    pub workout_type: WorkoutType,
+    pub landmarks: LandmarkSeries,
+    // frames of the rep within the recording, defaulting to all of it
+    pub start_frame: Option<u32>,
+    pub end_frame: Option<u32>,
+}
+
+// adds a recording of a good rep for reps of the same exercise to be compared to
+#[tracing::instrument(skip_all, err(Debug))]
+pub async fn upload_reference(
+    State(state): State<Arc<AppState>>,
+    headers: HeaderMap,
+    Json(upload): Json<ReferenceUpload>,
+) -> Result<(StatusCode, Json<ReferenceEntry>), AppError> {
+    check_admin(&state, &headers)?;
+
+    let start_frame = upload.start_frame.unwrap_or(0);
+    let end_frame = upload
+        .end_frame
+        .unwrap_or(upload.landmarks.frames.len().saturating_sub(1) as u32);
+    let trajectory = Trajectory::extract(&upload.landmarks, start_frame, end_frame)
+        .ok_or(AppError::InvalidReference)?;
+
+    let entry =
+        reference::upload_reference(&state.db, upload.name, upload.workout_type, trajectory)
+            .await
+            .map_err(AppError::InternalServerError)?;
+    tracing::info!(
+        "Added reference {:?} for {:?}",
+        entry.id,
+        entry.workout_type
+    );
+
+    Ok((StatusCode::CREATED, Json(entry)))
+}
+
+// admin routes need `Authorization: Bearer <ADMIN_TOKEN>`, so are shut if it isn't set
+fn check_admin(state: &AppState, headers: &HeaderMap) -> Result<(), AppError> {
+    let token = headers
+        .get(header::AUTHORIZATION)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.strip_prefix("Bearer "));
+
+    match (&state.admin_token, token) {
+        (Some(expected), Some(token)) if expected == token => Ok(()),
+        _ => Err(AppError::Unauthorized),
+    }
+}
@@ -59,7 +59,14 @@ pub async fn device_connect(
     state.link_tx.send(msg).await.map_app_err()?;
     let device_tx = res_rx.await.map_app_err()??;
 
-    Ok(ws.on_upgrade(|ws| async move {
-        _ = actors::device::device_task(state, ws, id, device_tx).await;
-    }))
+    // the device task refuses anything over the limit with an error and keeps going,
+    // but past this the websocket drops the connection before we ever see it
+    let max_size = state.limits.max_message_size * 2;
+
+    Ok(ws
+        .max_message_size(max_size)
+        .max_frame_size(max_size)
+        .on_upgrade(|ws| async move {
+            _ = actors::device::device_task(state, ws, id, device_tx).await;
+        }))
 }
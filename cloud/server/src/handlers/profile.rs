@@ -0,0 +1,148 @@
+use std::sync::Arc;
+
+use axum::{
+    extract::{Path, State},
+    Json,
+};
+use common_types::{UserId, WorkoutType};
+use serde::Deserialize;
+
+use crate::{
+    analysis::squat::{derive_features, Landmarks, ANGLE_COUNT},
+    error::AppError,
+    profile::{get_profile, set_profile, Calibration, Profile},
+    storage::download_landmarks,
+    types::state::AppState,
+    workout::{get_entry, now, parent_path, WorkoutStatus},
+};
+
+#[derive(Deserialize, Debug)]
+pub struct CalibrationRequest {
+    pub workout_id: String,
+    // counting from 1, like in the app
+    pub rep: u32,
+}
+
+#[tracing::instrument(skip(state), err(Debug))]
+pub async fn profile(
+    State(state): State<Arc<AppState>>,
+    Path(user_id): Path<String>,
+) -> Result<Json<Profile>, AppError> {
+    let user_id = UserId::from(user_id);
+
+    let profile = get_profile(&state.db, &user_id)
+        .await
+        .map_err(AppError::InternalServerError)?
+        .unwrap_or_default();
+
+    Ok(Json(profile))
+}
+
+// replaces the profile, apart from the calibration which has its own route
+#[tracing::instrument(skip(state), err(Debug))]
+pub async fn update_profile(
+    State(state): State<Arc<AppState>>,
+    Path(user_id): Path<String>,
+    Json(mut profile): Json<Profile>,
+) -> Result<Json<Profile>, AppError> {
+    let user_id = UserId::from(user_id);
+    if !profile.is_valid() {
+        return Err(AppError::InvalidProfile);
+    }
+
+    profile.calibration = get_profile(&state.db, &user_id)
+        .await
+        .map_err(AppError::InternalServerError)?
+        .and_then(|old| old.calibration);
+    set_profile(&state.db, &user_id, &profile)
+        .await
+        .map_err(AppError::InternalServerError)?;
+
+    Ok(Json(profile))
+}
+
+// makes a rep from one of the user's squats the one their others are scored against
+#[tracing::instrument(skip(state), err(Debug))]
+pub async fn calibrate(
+    State(state): State<Arc<AppState>>,
+    Path(user_id): Path<String>,
+    Json(CalibrationRequest { workout_id, rep }): Json<CalibrationRequest>,
+) -> Result<Json<Profile>, AppError> {
+    let user_id = UserId::from(user_id);
+    let parent_path = parent_path(&state.db, &user_id).map_err(AppError::InternalServerError)?;
+
+    let entry = get_entry(&state.db, &parent_path, &workout_id)
+        .await
+        .map_err(AppError::InternalServerError)?
+        .ok_or(AppError::NoSuchWorkout)?;
+    if entry.workout_type != WorkoutType::Squat {
+        return Err(AppError::NotASquat);
+    }
+    match entry.status {
+        WorkoutStatus::Completed => {}
+        WorkoutStatus::Recording | WorkoutStatus::Processing => return Err(AppError::WorkoutBusy),
+        WorkoutStatus::Failed { .. } => return Err(AppError::WorkoutFailed),
+    }
+    let landmarks_id = entry.landmarks_id.ok_or(AppError::NoLandmarks)?;
+    let bottom_frame = entry
+        .reps
+        .as_deref()
+        .and_then(|reps| reps.get((rep as usize).checked_sub(1)?))
+        .and_then(|feedback| feedback.bottom_frame)
+        .ok_or(AppError::NoSuchRep)?;
+
+    let series = download_landmarks(&state.client, &landmarks_id)
+        .await
+        .map_err(AppError::InternalServerError)?;
+    // squat landmarks are stored in the same order the scoring expects
+    let landmarks: Landmarks = series
+        .frames
+        .get(bottom_frame as usize)
+        .cloned()
+        .flatten()
+        .ok_or(AppError::NoSuchRep)?
+        .try_into()
+        .map_err(|_| AppError::NoLandmarks)?;
+
+    let features = derive_features(&landmarks);
+    let mut perfect_angles = [0.0; ANGLE_COUNT];
+    perfect_angles.copy_from_slice(&features[..ANGLE_COUNT]);
+
+    let mut profile = get_profile(&state.db, &user_id)
+        .await
+        .map_err(AppError::InternalServerError)?
+        .unwrap_or_default();
+    profile.calibration = Some(Calibration {
+        workout_id,
+        rep,
+        date: now(),
+        perfect_angles,
+    });
+    set_profile(&state.db, &user_id, &profile)
+        .await
+        .map_err(AppError::InternalServerError)?;
+
+    Ok(Json(profile))
+}
+
+// goes back to scoring against the default perfect squat
+#[tracing::instrument(skip(state), err(Debug))]
+pub async fn reset_calibration(
+    State(state): State<Arc<AppState>>,
+    Path(user_id): Path<String>,
+) -> Result<Json<Profile>, AppError> {
+    let user_id = UserId::from(user_id);
+
+    let Some(mut profile) = get_profile(&state.db, &user_id)
+        .await
+        .map_err(AppError::InternalServerError)?
+    else {
+        return Ok(Json(Profile::default()));
+    };
+    profile.calibration = None;
+    set_profile(&state.db, &user_id, &profile)
+        .await
+        .map_err(AppError::InternalServerError)?;
+
+    Ok(Json(profile))
+}
@@ -0,0 +1,364 @@
+use std::{io::ErrorKind, sync::Arc};
+
+use anyhow::Context;
+use axum::{
+    extract::{Path, Query, State},
+    http::{header, StatusCode},
+    response::IntoResponse,
+    Json,
+};
+use common_types::{Frame, UserId, VideoId, IMAGE_SIZE};
+use firestore::{
+    struct_path::paths, FirestoreConsistencySelector, FirestoreDb, FirestoreTransaction,
+    ParentPathBuilder,
+};
+use serde::Deserialize;
+use tokio::{fs::File, io::AsyncReadExt};
+use uuid::Uuid;
+
+use crate::{
+    analysis::landmarks::LandmarkSeries,
+    analyzer::{apply_analysis, call_ml, save_frames, Analysis},
+    clip::extract_clip,
+    constants::VIDEO_PATH,
+    error::{AppError, AppErrorExt},
+    profile::{get_profile, Profile},
+    storage::{
+        download_footage, download_landmarks, download_video, upload_landmarks, upload_video,
+    },
+    types::state::AppState,
+    workout::{
+        get_entry, list_entries, now, parent_path, save_revision, update_entry, update_entry_in,
+        WorkoutEntry, WorkoutStatus,
+    },
+};
+
+#[derive(Deserialize, Debug)]
+pub struct HistoryQuery {
+    pub analyzer: Option<String>,
+    pub analyzer_version: Option<String>,
+}
+
+// lists a user's workouts, which can be narrowed down to those from a given analyzer
+// e.g. to find the ones that need reprocessing after a version is deprecated
+#[tracing::instrument(skip(state), err(Debug))]
+pub async fn history(
+    State(state): State<Arc<AppState>>,
+    Path(user_id): Path<String>,
+    Query(HistoryQuery {
+        analyzer,
+        analyzer_version,
+    }): Query<HistoryQuery>,
+) -> Result<Json<Vec<WorkoutEntry>>, AppError> {
+    let user_id = UserId::from(user_id);
+    let parent_path = parent_path(&state.db, &user_id).map_err(AppError::InternalServerError)?;
+
+    let entries = list_entries(
+        &state.db,
+        &parent_path,
+        analyzer.as_deref(),
+        analyzer_version.as_deref(),
+    )
+    .await
+    .map_err(AppError::InternalServerError)?;
+
+    Ok(Json(entries))
+}
+
+// pose of every frame of the workout's current video, e.g. for drawing a skeleton over it
+#[tracing::instrument(skip(state), err(Debug))]
+pub async fn landmarks(
+    State(state): State<Arc<AppState>>,
+    Path((user_id, workout_id)): Path<(String, String)>,
+) -> Result<Json<LandmarkSeries>, AppError> {
+    let user_id = UserId::from(user_id);
+    let parent_path = parent_path(&state.db, &user_id).map_err(AppError::InternalServerError)?;
+
+    let entry = get_entry(&state.db, &parent_path, &workout_id)
+        .await
+        .map_err(AppError::InternalServerError)?
+        .ok_or(AppError::NoSuchWorkout)?;
+    let landmarks_id = entry.landmarks_id.ok_or(AppError::NoLandmarks)?;
+
+    let series = download_landmarks(&state.client, &landmarks_id)
+        .await
+        .map_err(AppError::InternalServerError)?;
+
+    Ok(Json(series))
+}
+
+// the part of the workout's video where a rep happened, numbered from 1 like in the app
+#[tracing::instrument(skip(state), err(Debug))]
+pub async fn rep_clip(
+    State(state): State<Arc<AppState>>,
+    Path((user_id, workout_id, rep)): Path<(String, String, usize)>,
+) -> Result<impl IntoResponse, AppError> {
+    let user_id = UserId::from(user_id);
+    let parent_path = parent_path(&state.db, &user_id).map_err(AppError::InternalServerError)?;
+
+    let entry = get_entry(&state.db, &parent_path, &workout_id)
+        .await
+        .map_err(AppError::InternalServerError)?
+        .ok_or(AppError::NoSuchWorkout)?;
+    let video_id = entry.video_id.ok_or(AppError::NoSuchRep)?;
+    let feedback = entry
+        .reps
+        .as_deref()
+        .and_then(|reps| reps.get(rep.checked_sub(1)?))
+        .ok_or(AppError::NoSuchRep)?;
+    let (Some(start_frame), Some(end_frame)) = (feedback.start_frame, feedback.end_frame) else {
+        return Err(AppError::NoSuchRep);
+    };
+
+    let video = download_video(&state.client, &video_id)
+        .await
+        .map_err(AppError::InternalServerError)?;
+    let clip = extract_clip(video, start_frame, end_frame)
+        .await
+        .map_err(AppError::InternalServerError)?;
+
+    Ok(([(header::CONTENT_TYPE, "video/mp4")], clip))
+}
+
+// re-runs the analyzer on the stored footage of a workout
+// the feedback it replaces is kept as a revision
+#[tracing::instrument(skip(state), err(Debug))]
+pub async fn reprocess(
+    State(state): State<Arc<AppState>>,
+    Path((user_id, workout_id)): Path<(String, String)>,
+) -> Result<StatusCode, AppError> {
+    let user_id = UserId::from(user_id);
+    let parent_path = parent_path(&state.db, &user_id).map_err(AppError::InternalServerError)?;
+
+    // read and written in one transaction, so that two requests can't both start on it
+    let mut transaction = state.db.begin_transaction().await.map_app_err()?;
+    let claimed = claim_entry(&state.db, &mut transaction, &parent_path, &workout_id).await;
+    let (entry, previous_status, footage_id) = match claimed {
+        Ok(claimed) => {
+            transaction.commit().await.map_app_err()?;
+            claimed
+        }
+        Err(e) => {
+            if let Err(e) = transaction.rollback().await {
+                tracing::warn!("Failed to roll back claiming {workout_id:?}: {e:?}");
+            }
+            return Err(e);
+        }
+    };
+
+    tokio::spawn(reprocess_task(
+        state,
+        user_id,
+        entry,
+        previous_status,
+        footage_id,
+    ));
+
+    Ok(StatusCode::ACCEPTED)
+}
+
+// marks the entry as processing, giving back the status it had and its footage
+async fn claim_entry(
+    db: &FirestoreDb,
+    transaction: &mut FirestoreTransaction<'_>,
+    parent_path: &ParentPathBuilder,
+    workout_id: &str,
+) -> Result<(WorkoutEntry, WorkoutStatus, VideoId), AppError> {
+    let transaction_db = db.clone_with_consistency_selector(
+        FirestoreConsistencySelector::Transaction(transaction.transaction_id().clone()),
+    );
+    let mut entry = get_entry(&transaction_db, parent_path, workout_id)
+        .await
+        .map_err(AppError::InternalServerError)?
+        .ok_or(AppError::NoSuchWorkout)?;
+
+    if entry.is_busy() {
+        return Err(AppError::WorkoutBusy);
+    }
+    let footage_id = entry.footage_id.clone().ok_or(AppError::NoFootage)?;
+
+    let previous_status = match std::mem::replace(&mut entry.status, WorkoutStatus::Processing) {
+        // if this attempt fails too, there's still nothing to go back to
+        WorkoutStatus::Processing => WorkoutStatus::Failed {
+            reason: "Analysis was interrupted".into(),
+        },
+        status => status,
+    };
+    entry.processing_at = Some(now());
+    update_entry_in(
+        db,
+        transaction,
+        parent_path,
+        &entry,
+        paths!(WorkoutEntry::{status, processing_at}),
+    )
+    .map_err(AppError::InternalServerError)?;
+
+    Ok((entry, previous_status, footage_id))
+}
+
+#[tracing::instrument(skip_all, err(Debug))]
+async fn reprocess_task(
+    state: Arc<AppState>,
+    user_id: UserId,
+    mut entry: WorkoutEntry,
+    previous_status: WorkoutStatus,
+    footage_id: VideoId,
+) -> anyhow::Result<()> {
+    let parent_path = parent_path(&state.db, &user_id)?;
+
+    let Err(e) = reprocess_footage(&state, &user_id, &mut entry, &footage_id).await else {
+        return Ok(());
+    };
+
+    // the previous feedback is still good, so the workout goes back to how it was,
+    // with a note for the app that this attempt failed
+    entry.status = previous_status;
+    entry.reprocess_failure = Some(format!("{e:#}"));
+    if let Err(e) = update_entry(
+        &state.db,
+        &parent_path,
+        &entry,
+        paths!(WorkoutEntry::{status, reprocess_failure}),
+    )
+    .await
+    {
+        tracing::warn!("Failed to record failure for {footage_id:?}: {e:?}");
+    }
+
+    Err(e)
+}
+
+async fn reprocess_footage(
+    state: &AppState,
+    user_id: &UserId,
+    entry: &mut WorkoutEntry,
+    footage_id: &VideoId,
+) -> anyhow::Result<()> {
+    // every run gets its own video, so that older revisions still point at theirs
+    let video_id = VideoId::from(Uuid::new_v4().to_string());
+    let folder_path: Arc<str> = format!("{VIDEO_PATH}/{video_id}.d").into();
+    let video_path = format!("{VIDEO_PATH}/{video_id}.mp4");
+    let footage_path = format!("{VIDEO_PATH}/{video_id}.raw");
+
+    let res = analyze_footage(
+        state,
+        user_id,
+        entry,
+        footage_id,
+        &video_id,
+        &folder_path,
+        &video_path,
+        &footage_path,
+    )
+    .await;
+
+    // whether or not it worked, none of these are needed any more
+    tracing::debug!("Deleting files for {:?}", video_id);
+    for path in [&video_path, &footage_path] {
+        if let Err(e) = tokio::fs::remove_file(path).await {
+            if e.kind() != ErrorKind::NotFound {
+                tracing::warn!("Failed to delete {path}: {e:?}");
+            }
+        }
+    }
+    if let Err(e) = tokio::fs::remove_dir_all(&*folder_path).await {
+        if e.kind() != ErrorKind::NotFound {
+            tracing::warn!("Failed to delete {}: {e:?}", &*folder_path);
+        }
+    }
+
+    let (analysis, profile, landmarks_id) = res?;
+
+    // keep the old feedback before it gets replaced
+    save_revision(&state.db, user_id, entry).await?;
+
+    let parent_path = parent_path(&state.db, user_id)?;
+    entry.status = WorkoutStatus::Completed;
+    entry.completed_at = Some(now());
+    entry.revision += 1;
+    entry.video_id = Some(video_id);
+    entry.landmarks_id = landmarks_id;
+    entry.reprocess_failure = None;
+    apply_analysis(state, entry, analysis, &profile).await?;
+    update_entry(
+        &state.db,
+        &parent_path,
+        entry,
+        paths!(WorkoutEntry::{status, completed_at, revision, analyzer, video_id, landmarks_id, reprocess_failure, reps, metrics, insight, hold}),
+    )
+    .await?;
+    tracing::debug!("Uploaded revision {} of {footage_id:?}", entry.revision);
+
+    Ok(())
+}
+
+// runs the analyzer on the footage and uploads what it made
+// leaves files behind at the paths it's given, which are for the caller to clean up
+#[allow(clippy::too_many_arguments)]
+async fn analyze_footage(
+    state: &AppState,
+    user_id: &UserId,
+    entry: &WorkoutEntry,
+    footage_id: &VideoId,
+    video_id: &VideoId,
+    folder_path: &Arc<str>,
+    video_path: &str,
+    footage_path: &str,
+) -> anyhow::Result<(Analysis, Profile, Option<VideoId>)> {
+    // saved in batches, so that only a few frames are in memory at once
+    const BATCH_FRAMES: usize = 30;
+
+    download_footage(&state.client, footage_id, footage_path).await?;
+    tracing::debug!("Downloaded footage {footage_id:?}");
+
+    tracing::debug!("Creating folder: {}", &**folder_path);
+    tokio::fs::create_dir(&**folder_path).await?;
+
+    let mut footage = File::open(footage_path).await?;
+    let mut count = 0;
+    loop {
+        let mut frames = Vec::with_capacity(BATCH_FRAMES);
+        while frames.len() < BATCH_FRAMES {
+            let mut buf = vec![0; IMAGE_SIZE];
+            match footage.read_exact(&mut buf).await {
+                Ok(_) => frames.push(Frame(buf)),
+                // a partial frame at the end is dropped, like chunks_exact would
+                Err(e) if e.kind() == ErrorKind::UnexpectedEof => break,
+                Err(e) => return Err(e.into()),
+            }
+        }
+        if frames.is_empty() {
+            break;
+        }
+
+        let len = frames.len();
+        save_frames(folder_path.clone(), frames, count).await?;
+        count += len;
+    }
+
+    let profile = get_profile(&state.db, user_id).await?.unwrap_or_default();
+    let analysis = call_ml(
+        video_path,
+        folder_path,
+        entry.workout_type,
+        profile.rep_angle(entry.workout_type),
+    )
+    .await
+    .context("Analyzer failed")?;
+
+    upload_video(&state.client, video_id, video_path).await?;
+    tracing::debug!("Uploaded video for {video_id:?}");
+
+    // landmarks line up with the video, so they share its ID
+    let landmarks_id = match &analysis.landmarks {
+        Some(landmarks) => {
+            upload_landmarks(&state.client, video_id, landmarks).await?;
+            tracing::debug!("Uploaded landmarks for {video_id:?}");
+            Some(video_id.clone())
+        }
+        None => None,
+    };
+
+    Ok((analysis, profile, landmarks_id))
+}
@@ -1,12 +1,23 @@
 mod actors;
+mod analysis;
+mod analyzer;
+mod clip;
 mod constants;
 mod error;
 mod handlers;
+mod profile;
+mod reference;
+mod storage;
 mod types;
+mod workout;
 
 use std::sync::Arc;
 
-use axum::{routing::get, Router};
+use analysis::squat::ScoringConfig;
+use axum::{
+    routing::{get, post},
+    Router,
+};
 use constants::CHANNEL_SIZE;
 use firestore::FirestoreDb;
 use google_cloud_default::WithAuthExt;
@@ -17,7 +28,7 @@ use tower_http::{
     cors::{self, CorsLayer},
     trace::TraceLayer,
 };
-use types::state::AppState;
+use types::{limits::Limits, state::AppState};
 
 pub async fn app() -> axum::Router {
     // cors layer
@@ -31,12 +42,40 @@ pub async fn app() -> axum::Router {
         db: open_db().await,
         client: open_storage().await,
         link_tx,
+        limits: Limits::from_env(),
+        scoring: ScoringConfig::from_env(),
+        keep_footage: matches!(std::env::var("KEEP_FOOTAGE").as_deref(), Ok("1" | "true")),
+        admin_token: std::env::var("ADMIN_TOKEN")
+            .ok()
+            .filter(|token| !token.is_empty()),
     });
 
     Router::new()
         .route("/", get(|| async { "Hello, world!" }))
         .route("/user", get(handlers::connect::user_connect))
         .route("/device", get(handlers::connect::device_connect))
+        .route(
+            "/users/:id/profile",
+            get(handlers::profile::profile).put(handlers::profile::update_profile),
+        )
+        .route(
+            "/users/:id/profile/calibration",
+            post(handlers::profile::calibrate).delete(handlers::profile::reset_calibration),
+        )
+        .route("/users/:id/workouts", get(handlers::workout::history))
+        .route(
+            "/users/:id/workouts/:workout_id/landmarks",
+            get(handlers::workout::landmarks),
+        )
+        .route(
+            "/users/:id/workouts/:workout_id/reps/:rep/clip",
+            get(handlers::workout::rep_clip),
+        )
+        .route(
+            "/users/:id/workouts/:workout_id/reprocess",
+            post(handlers::workout::reprocess),
+        )
+        .route("/admin/references", post(handlers::admin::upload_reference))
         .with_state(state)
         .layer(
             ServiceBuilder::new()
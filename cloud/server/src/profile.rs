@@ -0,0 +1,192 @@
+use std::ops::RangeInclusive;
+
+use common_types::{UserId, WorkoutType};
+use firestore::{FirestoreDb, FirestoreTimestamp};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    analysis::squat::{ScoringConfig, ANGLE_COUNT},
+    constants::PROFILE_COLLECTION,
+};
+
+// what the predictor uses when the user has no mobility limits
+const DEFAULT_REP_ANGLE: f64 = 130.0;
+// knee angle when standing up straight, near enough
+const STANDING_KNEE_ANGLE: f64 = 175.0;
+// anything outside of these is a typo or the wrong unit
+const HEIGHT_RANGE: RangeInclusive<f64> = 50.0..=250.0;
+const ANGLE_RANGE: RangeInclusive<f64> = 0.0..=180.0;
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum Experience {
+    Beginner,
+    // the default, so that users without a profile are scored as before
+    #[default]
+    Intermediate,
+    Advanced,
+}
+
+impl Experience {
+    // beginners get more leeway on their angles, advanced users less
+    fn tolerance_factor(self) -> f64 {
+        match self {
+            Self::Beginner => 1.5,
+            Self::Intermediate => 1.0,
+            Self::Advanced => 0.75,
+        }
+    }
+}
+
+/// What the analysis should know about a user's body, so that their reps are
+/// judged against what they can actually do.
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+#[serde(default)]
+pub struct Profile {
+    // in centimetres, only stored for now since the scoring is all angles, which don't
+    // change with how tall someone is
+    pub height: Option<f64>,
+    pub experience: Experience,
+    // smallest knee and hip angles the user can comfortably reach, in degrees
+    pub min_knee_angle: Option<f64>,
+    pub min_hip_angle: Option<f64>,
+    // only ever set by the server, from a workout the user picked
+    pub calibration: Option<Calibration>,
+}
+
+/// A squat the user is happy with, that their other squats are scored against.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Calibration {
+    pub workout_id: String,
+    // which rep of the workout, counting from 1
+    pub rep: u32,
+    pub date: FirestoreTimestamp,
+    // angles at the bottom of the rep, in the order of `derive_features`
+    pub perfect_angles: [f64; ANGLE_COUNT],
+}
+
+impl Profile {
+    // knee and hip angles in `perfect_angles`, on the left then the right
+    const KNEES: [usize; 2] = [0, 3];
+    const HIPS: [usize; 2] = [1, 4];
+
+    /// Whether everything the user entered is in a range that makes sense.
+    pub fn is_valid(&self) -> bool {
+        self.height
+            .into_iter()
+            .all(|height| HEIGHT_RANGE.contains(&height))
+            && [self.min_knee_angle, self.min_hip_angle]
+                .into_iter()
+                .flatten()
+                .all(|angle| ANGLE_RANGE.contains(&angle))
+    }
+
+    /// The thresholds to score this user's squats with.
+    pub fn scoring(&self, base: &ScoringConfig) -> ScoringConfig {
+        let mut scoring = base.clone();
+
+        if let Some(calibration) = &self.calibration {
+            scoring.perfect_angles = calibration.perfect_angles;
+        }
+        // don't expect the user to go deeper than they can
+        for (limit, indices) in [
+            (self.min_knee_angle, Self::KNEES),
+            (self.min_hip_angle, Self::HIPS),
+        ] {
+            if let Some(limit) = limit {
+                for i in indices {
+                    scoring.perfect_angles[i] = scoring.perfect_angles[i].max(limit);
+                }
+            }
+        }
+        scoring.angle_tolerance *= self.experience.tolerance_factor();
+
+        scoring
+    }
+
+    /// Knee angle that a rep has to bend past to be counted, if it isn't the default.
+    pub fn rep_angle(&self, workout_type: WorkoutType) -> Option<f64> {
+        // halfway down to the deepest the user can go
+        let limit = self.min_knee_angle?;
+        let angle = (STANDING_KNEE_ANGLE + limit) / 2.0;
+
+        (workout_type == WorkoutType::Squat && angle > DEFAULT_REP_ANGLE).then_some(angle)
+    }
+}
+
+pub async fn get_profile(db: &FirestoreDb, user_id: &UserId) -> anyhow::Result<Option<Profile>> {
+    let profile = db
+        .fluent()
+        .select()
+        .by_id_in(PROFILE_COLLECTION)
+        .obj()
+        .one(user_id.as_ref())
+        .await?;
+
+    Ok(profile)
+}
+
+// creates the profile if the user doesn't have one yet
+pub async fn set_profile(
+    db: &FirestoreDb,
+    user_id: &UserId,
+    profile: &Profile,
+) -> anyhow::Result<()> {
+    db.fluent()
+        .update()
+        .in_col(PROFILE_COLLECTION)
+        .document_id(user_id.as_ref())
+        .object(profile)
+        .execute::<Profile>()
+        .await?;
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_default_profile() {
+        let base = ScoringConfig::default();
+
+        assert_eq!(Profile::default().scoring(&base), base);
+        assert_eq!(Profile::default().rep_angle(WorkoutType::Squat), None);
+    }
+
+    #[test]
+    fn test_mobility_limits() {
+        let base = ScoringConfig::default();
+        let profile = Profile {
+            experience: Experience::Advanced,
+            min_knee_angle: Some(110.0),
+            ..Default::default()
+        };
+        let scoring = profile.scoring(&base);
+
+        assert_eq!(scoring.perfect_angles[0], 110.0);
+        assert_eq!(scoring.perfect_angles[3], 110.0);
+        assert_eq!(scoring.perfect_angles[1], base.perfect_angles[1]);
+        assert_eq!(scoring.angle_tolerance, base.angle_tolerance * 0.75);
+        assert_eq!(profile.rep_angle(WorkoutType::Squat), Some(142.5));
+        assert_eq!(profile.rep_angle(WorkoutType::Pushup), None);
+    }
+
+    #[test]
+    fn test_valid() {
+        let profile = Profile {
+            height: Some(180.0),
+            min_knee_angle: Some(110.0),
+            ..Default::default()
+        };
+        assert!(profile.is_valid());
+
+        // in metres rather than centimetres
+        let profile = Profile {
+            height: Some(1.8),
+            ..Default::default()
+        };
+        assert!(!profile.is_valid());
+    }
+}
@@ -0,0 +1,62 @@
+use common_types::WorkoutType;
+use firestore::{FirestoreDb, FirestoreTimestamp};
+use serde::{Deserialize, Serialize};
+
+use crate::{analysis::trajectory::Trajectory, constants::REFERENCE_COLLECTION, workout::now};
+
+/// A rep done with good form, that reps of the same exercise are compared to.
+#[derive(Serialize, Deserialize, Debug)]
+pub struct ReferenceEntry {
+    #[serde(alias = "_firestore_id")]
+    pub id: Option<String>,
+    // to tell the recordings apart, e.g. who did the rep
+    pub name: String,
+    #[serde(rename = "type")]
+    pub workout_type: WorkoutType,
+    pub date: FirestoreTimestamp,
+    pub trajectory: Trajectory,
+}
+
+pub async fn upload_reference(
+    db: &FirestoreDb,
+    name: String,
+    workout_type: WorkoutType,
+    trajectory: Trajectory,
+) -> anyhow::Result<ReferenceEntry> {
+    let entry = ReferenceEntry {
+        id: None,
+        name,
+        workout_type,
+        date: now(),
+        trajectory,
+    };
+
+    let entry = db
+        .fluent()
+        .insert()
+        .into(REFERENCE_COLLECTION)
+        .generate_document_id()
+        .object(&entry)
+        .execute::<ReferenceEntry>()
+        .await?;
+
+    Ok(entry)
+}
+
+// trajectories of every reference rep of an exercise
+pub async fn list_references(
+    db: &FirestoreDb,
+    workout_type: WorkoutType,
+) -> anyhow::Result<Vec<Trajectory>> {
+    let entries: Vec<ReferenceEntry> = db
+        .fluent()
+        .select()
+        .from(REFERENCE_COLLECTION)
+        // stored under its serde name
+        .filter(|q| q.field("type").eq(workout_type))
+        .obj()
+        .query()
+        .await?;
+
+    Ok(entries.into_iter().map(|entry| entry.trajectory).collect())
+}
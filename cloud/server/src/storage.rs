@@ -0,0 +1,200 @@
+use common_types::VideoId;
+use futures::{stream, StreamExt};
+use google_cloud_storage::{
+    client::Client as StorageClient,
+    http::objects::{
+        download::Range,
+        get::GetObjectRequest,
+        upload::{Media, UploadObjectRequest, UploadType},
+    },
+};
+
+use tokio::{
+    fs::File,
+    io::{AsyncReadExt, AsyncWriteExt},
+};
+
+use crate::{
+    analysis::landmarks::{self, LandmarkSeries},
+    constants::BUCKET_NAME,
+};
+
+// raw footage is kept as every frame's RGB565 bytes back to back
+const FOOTAGE_FOLDER: &str = "raw";
+const VIDEO_FOLDER: &str = "videos";
+// see analysis::landmarks for the format
+const LANDMARK_FOLDER: &str = "landmarks";
+
+// footage can be over a gigabyte, so it goes to and from storage this much at a time
+const FOOTAGE_CHUNK_SIZE: usize = 1 << 20;
+
+pub async fn upload_video(
+    client: &StorageClient,
+    video_id: &VideoId,
+    video_path: &str,
+) -> anyhow::Result<()> {
+    tracing::debug!("Video path is: {video_path}");
+    let video = tokio::fs::read(&video_path).await?;
+
+    upload_object(
+        client,
+        format!("{VIDEO_FOLDER}/{video_id}"),
+        "video/mp4",
+        video,
+    )
+    .await
+}
+
+pub async fn download_video(client: &StorageClient, video_id: &VideoId) -> anyhow::Result<Vec<u8>> {
+    download_object(client, format!("{VIDEO_FOLDER}/{video_id}")).await
+}
+
+pub async fn upload_footage(
+    client: &StorageClient,
+    footage_id: &VideoId,
+    footage_path: &str,
+) -> anyhow::Result<()> {
+    tracing::debug!("Footage path is: {footage_path}");
+    let file = File::open(footage_path).await?;
+
+    let chunks = stream::try_unfold(file, |mut file| async move {
+        let mut chunk = vec![0; FOOTAGE_CHUNK_SIZE];
+        let len = file.read(&mut chunk).await?;
+        if len == 0 {
+            return Ok(None);
+        }
+
+        chunk.truncate(len);
+        Ok::<_, std::io::Error>(Some((chunk, file)))
+    });
+
+    let upload_type = UploadType::Simple(Media {
+        name: format!("{FOOTAGE_FOLDER}/{footage_id}").into(),
+        content_type: "application/octet-stream".into(),
+        content_length: None,
+    });
+
+    client
+        .upload_streamed_object(
+            &UploadObjectRequest {
+                bucket: BUCKET_NAME.into(),
+                ..Default::default()
+            },
+            chunks,
+            &upload_type,
+        )
+        .await?;
+
+    Ok(())
+}
+
+// saves the footage to `footage_path`, rather than holding all of it at once
+pub async fn download_footage(
+    client: &StorageClient,
+    footage_id: &VideoId,
+    footage_path: &str,
+) -> anyhow::Result<()> {
+    let chunks = client
+        .download_streamed_object(
+            &GetObjectRequest {
+                bucket: BUCKET_NAME.into(),
+                object: format!("{FOOTAGE_FOLDER}/{footage_id}"),
+                ..Default::default()
+            },
+            &Range::default(),
+        )
+        .await?;
+    futures::pin_mut!(chunks);
+
+    let mut file = File::create(footage_path).await?;
+    while let Some(chunk) = chunks.next().await {
+        file.write_all(&chunk?).await?;
+    }
+    file.flush().await?;
+
+    Ok(())
+}
+
+pub async fn upload_landmarks(
+    client: &StorageClient,
+    landmarks_id: &VideoId,
+    series: &LandmarkSeries,
+) -> anyhow::Result<()> {
+    upload_object(
+        client,
+        format!("{LANDMARK_FOLDER}/{landmarks_id}"),
+        "application/octet-stream",
+        landmarks::encode(series)?,
+    )
+    .await
+}
+
+pub async fn download_landmarks(
+    client: &StorageClient,
+    landmarks_id: &VideoId,
+) -> anyhow::Result<LandmarkSeries> {
+    let buf = download_object(client, format!("{LANDMARK_FOLDER}/{landmarks_id}")).await?;
+
+    landmarks::decode(&buf)
+}
+
+async fn download_object(client: &StorageClient, name: String) -> anyhow::Result<Vec<u8>> {
+    let data = client
+        .download_object(
+            &GetObjectRequest {
+                bucket: BUCKET_NAME.into(),
+                object: name,
+                ..Default::default()
+            },
+            &Range::default(),
+        )
+        .await?;
+
+    Ok(data)
+}
+
+async fn upload_object(
+    client: &StorageClient,
+    name: String,
+    content_type: &str,
+    data: Vec<u8>,
+) -> anyhow::Result<()> {
+    let upload_type = UploadType::Simple(Media {
+        name: name.into(),
+        content_type: content_type.to_owned().into(),
+        content_length: None,
+    });
+
+    // NOTE: using firebase emulators:exec breaks this for some reason!!!
+    client
+        .upload_object(
+            &UploadObjectRequest {
+                bucket: BUCKET_NAME.into(),
+                ..Default::default()
+            },
+            data,
+            &upload_type,
+        )
+        .await?;
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use uuid::Uuid;
+
+    use crate::open_storage;
+
+    use super::*;
+    #[tokio::test]
+    async fn test_video_upload() -> anyhow::Result<()> {
+        let client = open_storage().await;
+        let video_id = VideoId::from(Uuid::new_v4().to_string());
+        let video_path = "../.video/test.mp4";
+
+        upload_video(&client, &video_id, video_path).await?;
+
+        Ok(())
+    }
+}
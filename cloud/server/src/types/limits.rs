@@ -0,0 +1,58 @@
+use std::{str::FromStr, time::Duration};
+
+use crate::constants::*;
+
+/// Limits on what a single device is allowed to send.
+///
+/// Each one can be overridden with the environment variable of the same name, e.g. `MAX_BATCH_FRAMES`.
+#[derive(Debug, Clone)]
+pub struct Limits {
+    /// Largest websocket message accepted, in bytes.
+    pub max_message_size: usize,
+    /// Most frames allowed in a single `Frames` request.
+    pub max_batch_frames: usize,
+    /// Most frames allowed in a single video.
+    pub max_video_frames: usize,
+    /// Longest a video can be recorded for, measured from its `Start` request.
+    pub max_video_length: Duration,
+    /// Most bytes per second a device can send, averaged over `BANDWIDTH_WINDOW`.
+    pub max_bandwidth: usize,
+}
+
+impl Default for Limits {
+    fn default() -> Self {
+        Self {
+            max_message_size: DEFAULT_MAX_MESSAGE_SIZE,
+            max_batch_frames: DEFAULT_MAX_BATCH_FRAMES,
+            max_video_frames: DEFAULT_MAX_VIDEO_FRAMES,
+            max_video_length: DEFAULT_MAX_VIDEO_LENGTH,
+            max_bandwidth: DEFAULT_MAX_BANDWIDTH,
+        }
+    }
+}
+
+impl Limits {
+    pub fn from_env() -> Self {
+        let default = Self::default();
+
+        Self {
+            max_message_size: env_or("MAX_MESSAGE_SIZE", default.max_message_size),
+            max_batch_frames: env_or("MAX_BATCH_FRAMES", default.max_batch_frames),
+            max_video_frames: env_or("MAX_VIDEO_FRAMES", default.max_video_frames),
+            max_video_length: Duration::from_secs(env_or(
+                "MAX_VIDEO_LENGTH",
+                default.max_video_length.as_secs(),
+            )),
+            max_bandwidth: env_or("MAX_BANDWIDTH", default.max_bandwidth),
+        }
+    }
+}
+
+fn env_or<T: FromStr>(name: &str, default: T) -> T {
+    match std::env::var(name) {
+        Ok(value) => value
+            .parse()
+            .unwrap_or_else(|_| panic!("{name} is not a valid number")),
+        Err(_) => default,
+    }
+}
@@ -2,11 +2,19 @@ use firestore::FirestoreDb;
 use google_cloud_storage::client::Client as StorageClient;
 use tokio::sync::mpsc;
 
-use super::message::LinkMessage;
+use crate::analysis::squat::ScoringConfig;
+
+use super::{limits::Limits, message::LinkMessage};
 
 /// Shared state used by all routes.
 pub struct AppState {
     pub db: FirestoreDb,
     pub client: StorageClient,
     pub link_tx: mpsc::Sender<LinkMessage>,
+    pub limits: Limits,
+    pub scoring: ScoringConfig,
+    // whether raw frames are kept in storage so that workouts can be reprocessed
+    pub keep_footage: bool,
+    // needed by admin routes, which are disabled without it
+    pub admin_token: Option<String>,
 }
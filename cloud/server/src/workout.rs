@@ -0,0 +1,283 @@
+use anyhow::Context;
+use common_types::{
+    DeviceId, Feedback, HoldFeedback, SetInsight, UserId, VideoId, WorkoutMetrics, WorkoutType,
+};
+use firestore::{
+    struct_path::paths, FirestoreDb, FirestoreTimestamp, FirestoreTransaction, ParentPathBuilder,
+};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    analyzer::AnalyzerInfo,
+    constants::{PROCESSING_TIMEOUT, REVISION_COLLECTION, USER_COLLECTION, WORKOUT_COLLECTION},
+};
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(tag = "state", rename_all = "snake_case")]
+pub enum WorkoutStatus {
+    Recording,
+    Processing,
+    Completed,
+    Failed { reason: String },
+}
+
+impl Default for WorkoutStatus {
+    // entries from before the status was tracked
+    fn default() -> Self {
+        Self::Completed
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct WorkoutEntry {
+    #[serde(alias = "_firestore_id")]
+    pub id: Option<String>,
+    // when recording started
+    pub date: FirestoreTimestamp,
+    #[serde(rename = "type")]
+    pub workout_type: WorkoutType,
+    // older entries don't have a device or status
+    pub device_id: Option<DeviceId>,
+    #[serde(default)]
+    pub status: WorkoutStatus,
+    pub processing_at: Option<FirestoreTimestamp>,
+    pub completed_at: Option<FirestoreTimestamp>,
+    pub failed_at: Option<FirestoreTimestamp>,
+    // seconds between the start of recording and the video being done
+    pub duration: Option<f64>,
+    pub frame_count: Option<u32>,
+    // raw frames in storage, only kept if the server is configured to
+    pub footage_id: Option<VideoId>,
+    // how many times the footage has been reprocessed
+    #[serde(default)]
+    pub revision: u32,
+    // why reprocessing last failed, if it did, in which case the feedback is from before it
+    pub reprocess_failure: Option<String>,
+    pub analyzer: Option<AnalyzerInfo>,
+    pub video_id: Option<VideoId>,
+    // pose of every frame in the video, if the analyzer tracked it
+    pub landmarks_id: Option<VideoId>,
+    pub reps: Option<Vec<Feedback>>,
+    // averages of the metrics of each rep
+    pub metrics: Option<WorkoutMetrics>,
+    pub insight: Option<SetInsight>,
+    // instead of reps, for exercises that are held
+    pub hold: Option<HoldFeedback>,
+}
+
+// feedback that has been replaced by reprocessing, kept under the workout
+#[derive(Serialize, Deserialize, Debug)]
+pub struct Revision {
+    pub revision: u32,
+    // when the feedback was produced
+    pub date: Option<FirestoreTimestamp>,
+    pub analyzer: Option<AnalyzerInfo>,
+    pub video_id: Option<VideoId>,
+    pub landmarks_id: Option<VideoId>,
+    pub reps: Option<Vec<Feedback>>,
+    pub metrics: Option<WorkoutMetrics>,
+    pub insight: Option<SetInsight>,
+    pub hold: Option<HoldFeedback>,
+}
+
+impl WorkoutEntry {
+    // whether something is still working on the entry, rather than having been cut off
+    // by the server restarting
+    pub fn is_busy(&self) -> bool {
+        match self.status {
+            WorkoutStatus::Recording => true,
+            // one started in the future is still going, as far as we can tell
+            WorkoutStatus::Processing => self.processing_at.as_ref().is_some_and(
+                |at| !matches!((now().0 - at.0).to_std(), Ok(age) if age >= PROCESSING_TIMEOUT),
+            ),
+            WorkoutStatus::Completed | WorkoutStatus::Failed { .. } => false,
+        }
+    }
+}
+
+pub fn now() -> FirestoreTimestamp {
+    FirestoreTimestamp::from(chrono::offset::Utc::now())
+}
+
+pub fn parent_path(db: &FirestoreDb, user_id: &UserId) -> anyhow::Result<ParentPathBuilder> {
+    Ok(db.parent_path(USER_COLLECTION, user_id.as_ref())?)
+}
+
+// uploads the workout entry for a video that has just started recording
+pub async fn upload_entry(
+    db: &FirestoreDb,
+    parent_path: &ParentPathBuilder,
+    device_id: DeviceId,
+    workout_type: WorkoutType,
+) -> anyhow::Result<WorkoutEntry> {
+    let entry = WorkoutEntry {
+        id: None,
+        date: now(),
+        workout_type,
+        device_id: Some(device_id),
+        status: WorkoutStatus::Recording,
+        processing_at: None,
+        completed_at: None,
+        failed_at: None,
+        duration: None,
+        frame_count: None,
+        footage_id: None,
+        revision: 0,
+        reprocess_failure: None,
+        analyzer: None,
+        video_id: None,
+        landmarks_id: None,
+        reps: None,
+        metrics: None,
+        insight: None,
+        hold: None,
+    };
+
+    let entry = db
+        .fluent()
+        .insert()
+        .into(WORKOUT_COLLECTION)
+        .generate_document_id()
+        .parent(parent_path)
+        .object(&entry)
+        .execute::<WorkoutEntry>()
+        .await?;
+
+    Ok(entry)
+}
+
+pub async fn get_entry(
+    db: &FirestoreDb,
+    parent_path: &ParentPathBuilder,
+    workout_id: &str,
+) -> anyhow::Result<Option<WorkoutEntry>> {
+    let entry = db
+        .fluent()
+        .select()
+        .by_id_in(WORKOUT_COLLECTION)
+        .parent(parent_path)
+        .obj()
+        .one(workout_id)
+        .await?;
+
+    Ok(entry)
+}
+
+// all of a user's workouts in order, optionally only those from a given analyzer
+pub async fn list_entries(
+    db: &FirestoreDb,
+    parent_path: &ParentPathBuilder,
+    analyzer: Option<&str>,
+    analyzer_version: Option<&str>,
+) -> anyhow::Result<Vec<WorkoutEntry>> {
+    let mut entries: Vec<WorkoutEntry> = db
+        .fluent()
+        .select()
+        .from(WORKOUT_COLLECTION)
+        .parent(parent_path)
+        .filter(|q| {
+            q.for_all([
+                analyzer.and_then(|name| q.field("analyzer.name").eq(name)),
+                analyzer_version.and_then(|version| q.field("analyzer.version").eq(version)),
+            ])
+        })
+        .obj()
+        .query()
+        .await?;
+
+    // sorted here rather than in the query, which would need an index for every filter
+    entries.sort_by(|a, b| a.date.0.cmp(&b.date.0));
+
+    Ok(entries)
+}
+
+// writes only the given fields of the entry
+pub async fn update_entry(
+    db: &FirestoreDb,
+    parent_path: &ParentPathBuilder,
+    entry: &WorkoutEntry,
+    fields: Vec<String>,
+) -> anyhow::Result<()> {
+    db.fluent()
+        .update()
+        .fields(fields)
+        .in_col(WORKOUT_COLLECTION)
+        .document_id(entry.id.as_ref().context("Entry has no ID")?)
+        .parent(parent_path)
+        .object(entry)
+        .execute::<WorkoutEntry>()
+        .await?;
+
+    Ok(())
+}
+
+// like `update_entry`, but only written once the transaction is committed
+pub fn update_entry_in(
+    db: &FirestoreDb,
+    transaction: &mut FirestoreTransaction<'_>,
+    parent_path: &ParentPathBuilder,
+    entry: &WorkoutEntry,
+    fields: Vec<String>,
+) -> anyhow::Result<()> {
+    db.fluent()
+        .update()
+        .fields(fields)
+        .in_col(WORKOUT_COLLECTION)
+        .document_id(entry.id.as_ref().context("Entry has no ID")?)
+        .parent(parent_path)
+        .object(entry)
+        .add_to_transaction(transaction)?;
+
+    Ok(())
+}
+
+pub async fn record_failure(
+    db: &FirestoreDb,
+    parent_path: &ParentPathBuilder,
+    entry: &mut WorkoutEntry,
+    reason: String,
+) -> anyhow::Result<()> {
+    entry.status = WorkoutStatus::Failed { reason };
+    entry.failed_at = Some(now());
+    update_entry(
+        db,
+        parent_path,
+        entry,
+        paths!(WorkoutEntry::{status, failed_at}),
+    )
+    .await
+}
+
+// saves the current feedback of the entry so that it can be replaced
+pub async fn save_revision(
+    db: &FirestoreDb,
+    user_id: &UserId,
+    entry: &WorkoutEntry,
+) -> anyhow::Result<()> {
+    let workout_id = entry.id.as_ref().context("Entry has no ID")?;
+    let parent_path = parent_path(db, user_id)?.at(WORKOUT_COLLECTION, workout_id.as_str())?;
+
+    let revision = Revision {
+        revision: entry.revision,
+        date: entry.completed_at.clone(),
+        analyzer: entry.analyzer.clone(),
+        video_id: entry.video_id.clone(),
+        landmarks_id: entry.landmarks_id.clone(),
+        reps: entry.reps.clone(),
+        metrics: entry.metrics,
+        insight: entry.insight.clone(),
+        hold: entry.hold.clone(),
+    };
+
+    // overwrites what a failed attempt might have left behind
+    db.fluent()
+        .update()
+        .in_col(REVISION_COLLECTION)
+        .document_id(entry.revision.to_string())
+        .parent(&parent_path)
+        .object(&revision)
+        .execute::<Revision>()
+        .await?;
+
+    Ok(())
+}
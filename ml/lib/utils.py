@@ -1,4 +1,6 @@
 import cv2
+import hashlib
+import json
 import math
 import numpy as np
 import pandas as pd
@@ -202,4 +204,31 @@ def draw_XY_angle(frame, text_a1, text_a2, text_a3, a1, a2, a3):
 def draw_Count(frame, text_a1, a1):
   # Display angle
   cv2.putText(frame, text_a1 + str(round(a1)), (0, 100), cv2.FONT_HERSHEY_SIMPLEX, 1, (0, 0, 255), 3, cv2.LINE_AA)
-  
\ No newline at end of file
+  
+
+####### Analyzer provenance #######
+def file_hash(path):
+  sha = hashlib.sha256()
+  with open(path, 'rb') as f:
+    for chunk in iter(lambda: f.read(1 << 16), b''):
+      sha.update(chunk)
+  return sha.hexdigest()
+
+def landmark_dicts(landmarks):
+  return [{'x': l.x, 'y': l.y, 'visibility': l.visibility} for l in landmarks]
+
+# Prints the output along with what produced it, which the server stores with the workout
+# model_path is whatever decides the classes, so the script itself for rule based analyzers
+# output is either reps=[feedback] or squat_predictions=[{landmarks, probabilities}] for the server to score,
+# or pose_only={} when the server works everything out from the landmarks
+# landmarks={names, frames} can also be given, with the landmarks of every frame or None if no pose was found
+def print_report(name, version, model_path, **output):
+  report = {
+    'analyzer': {
+      'name': name,
+      'version': version,
+      'model_hash': file_hash(model_path),
+    },
+  }
+  report.update(output)
+  print(json.dumps(report))
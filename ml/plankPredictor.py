@@ -0,0 +1,73 @@
+import cv2
+import mediapipe as mp
+import sys
+import lib.utils as utils
+
+ANALYZER_NAME = 'plank_tracker'
+ANALYZER_VERSION = '1.0.0'
+
+arguments = sys.argv[1:]
+
+videoFile = arguments[0]
+destDir = arguments[1]
+mp_drawing = mp.solutions.drawing_utils
+mp_pose = mp.solutions.pose
+
+####### Pose Detection #######
+# Only the pose is tracked here, the server works out the hold and alignment from the landmarks
+# (see cloud/server/src/analysis/plank.rs)
+cap = cv2.VideoCapture(videoFile)
+confident_threshold = 0.5
+landmarkNames = ['left_shoulder', 'right_shoulder', 'left_hip', 'right_hip', 'left_knee', 'right_knee',
+                 'left_ankle', 'right_ankle']
+frameLandmarks = []
+
+width = int(cap.get(cv2.CAP_PROP_FRAME_WIDTH))
+height = int(cap.get(cv2.CAP_PROP_FRAME_HEIGHT))
+
+outVideo = cv2.VideoWriter(destDir, cv2.VideoWriter_fourcc(
+    *'avc1'), 30, (width, height), True)
+
+with mp_pose.Pose(min_detection_confidence=confident_threshold, min_tracking_confidence=confident_threshold) as pose:
+    while cap.isOpened():
+        ret, frame = cap.read()
+
+        # Recolor image to RGB
+        try:
+            image = cv2.cvtColor(frame, cv2.COLOR_BGR2RGB)
+            image.flags.writeable = False
+        except:
+            break
+
+        frameLandmarks.append(None)
+
+        # Make detection
+        results = pose.process(image)
+
+        # Recolor back to BGR
+        image.flags.writeable = True
+        image = cv2.cvtColor(image, cv2.COLOR_RGB2BGR)
+
+        # Render detections
+        mp_drawing.draw_landmarks(image, results.pose_landmarks, mp_pose.POSE_CONNECTIONS,
+                                  mp_drawing.DrawingSpec(
+                                      color=(245, 117, 66), thickness=2, circle_radius=2),
+                                  mp_drawing.DrawingSpec(color=(245, 66, 230), thickness=2, circle_radius=2))
+
+        # Extract landmarks
+        if results.pose_landmarks:
+            lm = results.pose_landmarks.landmark
+            pl = mp_pose.PoseLandmark
+
+            frameFeatures = [lm[pl.LEFT_SHOULDER.value], lm[pl.RIGHT_SHOULDER.value], lm[pl.LEFT_HIP.value], lm[pl.RIGHT_HIP.value],
+                             lm[pl.LEFT_KNEE.value], lm[pl.RIGHT_KNEE.value], lm[pl.LEFT_ANKLE.value], lm[pl.RIGHT_ANKLE.value]]
+            frameLandmarks[-1] = utils.landmark_dicts(frameFeatures)
+
+        # Write into frame
+        outVideo.write(image)
+
+# Output video
+outVideo.release()
+
+utils.print_report(ANALYZER_NAME, ANALYZER_VERSION, __file__, pose_only={},
+                   landmarks={'names': landmarkNames, 'frames': frameLandmarks})
@@ -4,6 +4,9 @@ import sys
 import lib.utils as utils
 import json
 
+ANALYZER_NAME = 'pushup_predictor'
+ANALYZER_VERSION = '1.2.0'
+
 arguments = sys.argv[1:]
 
 videoFile = arguments[0]
@@ -29,6 +32,15 @@ smallestKneeAngle = S_angle
 # Store an array of array of features for squatRec[i][j] represent ith squat and jth keyth points of the smallest knee angle frame
 exerciseRec = [[]]
 i = 0
+# Landmarks in features_i for every frame, so the server can store the whole pose over time
+landmarkNames = ['nose', 'left_shoulder', 'right_shoulder', 'left_elbow', 'right_elbow', 'left_wrist', 'right_wrist',
+                 'left_hip', 'right_hip', 'left_knee', 'right_knee', 'left_ankle', 'right_ankle']
+frameLandmarks = []
+# Frames where each rep starts, bottoms out and ends, parallel to exerciseRec
+# A rep starts from the most extended frame while standing before it
+repFrames = [{}]
+topAngle = 0
+topFrame = 0
 
 width = int(cap.get(cv2.CAP_PROP_FRAME_WIDTH))
 height = int(cap.get(cv2.CAP_PROP_FRAME_HEIGHT))
@@ -50,6 +62,9 @@ with mp_pose.Pose(min_detection_confidence=confident_threshold, min_tracking_con
         except:
             break
 
+        frameLandmarks.append(None)
+        frameIndex = len(frameLandmarks) - 1
+
         # Make detection
         results = pose.process(image)
 
@@ -68,6 +83,10 @@ with mp_pose.Pose(min_detection_confidence=confident_threshold, min_tracking_con
             lm = results.pose_landmarks.landmark
             pl = mp_pose.PoseLandmark
 
+            frameFeatures = [lm[pl.NOSE.value], lm[pl.LEFT_SHOULDER.value], lm[pl.RIGHT_SHOULDER.value], lm[pl.LEFT_ELBOW.value], lm[pl.RIGHT_ELBOW.value], lm[pl.LEFT_WRIST.value],
+                             lm[pl.RIGHT_WRIST.value], lm[pl.LEFT_HIP.value], lm[pl.RIGHT_HIP.value], lm[pl.LEFT_KNEE.value], lm[pl.RIGHT_KNEE.value], lm[pl.LEFT_ANKLE.value], lm[pl.RIGHT_ANKLE.value]]
+            frameLandmarks[-1] = utils.landmark_dicts(frameFeatures)
+
             # Compute angle
             sl = [lm[pl.LEFT_SHOULDER.value].x, lm[pl.LEFT_SHOULDER.value].y]
             el = [lm[pl.LEFT_ELBOW.value].x, lm[pl.LEFT_ELBOW.value].y]
@@ -90,20 +109,27 @@ with mp_pose.Pose(min_detection_confidence=confident_threshold, min_tracking_con
             if (minElbow < S_angle):  # down position
                 if (state_count == 0):
                     state_count += 1
+                    repFrames[i]['start_frame'] = topFrame
 
                 if (minElbow < smallestKneeAngle):
                     smallestKneeAngle = minElbow
-                    features_i = [lm[pl.NOSE.value], lm[pl.LEFT_SHOULDER.value], lm[pl.RIGHT_SHOULDER.value], lm[pl.LEFT_ELBOW.value], lm[pl.RIGHT_ELBOW.value], lm[pl.LEFT_WRIST.value],
-                                  lm[pl.RIGHT_WRIST.value], lm[pl.LEFT_HIP.value], lm[pl.RIGHT_HIP.value], lm[pl.LEFT_KNEE.value], lm[pl.RIGHT_KNEE.value], lm[pl.LEFT_ANKLE.value], lm[pl.RIGHT_ANKLE.value]]
-                    exerciseRec[i] = features_i
+                    exerciseRec[i] = frameFeatures
+                    repFrames[i]['bottom_frame'] = frameIndex
 
             elif (minElbow > S_angle):  # stand position
                 if (state_count == 1):   # Already down position
                     state_count = 0
                     pushup_count += 1
+                    repFrames[i]['end_frame'] = frameIndex
+                    repFrames.append({})
+                    topAngle = minElbow
+                    topFrame = frameIndex
                     i += 1
                     smallestKneeAngle = minElbow
                     exerciseRec.append([])
+                elif (minElbow > topAngle):
+                    topAngle = minElbow
+                    topFrame = frameIndex
 
         except:
             pass
@@ -178,7 +204,8 @@ for exer_i_th in range(len(inputPreData)):
     fb_i = {
         'ex_number': exer_i_th + 1,
         'class': "",
-        'correction': ""
+        'correction': "",
+        **repFrames[exer_i_th],
     }
     for i in range(len(groupClasses)):
         if (i == len(groupClasses) - 1):
@@ -190,4 +217,5 @@ for exer_i_th in range(len(inputPreData)):
 
     feedback.append(fb_i)
 
-print(json.dumps(feedback))
+utils.print_report(ANALYZER_NAME, ANALYZER_VERSION, __file__, reps=feedback,
+                   landmarks={'names': landmarkNames, 'frames': frameLandmarks})
@@ -7,6 +7,10 @@ import json
 import sys
 import lib.utils as utils
 
+ANALYZER_NAME = 'squat_predictor'
+ANALYZER_VERSION = '2.3.0'
+MODEL_PATH = '.ml/squatModel_lessClass.h5'
+
 arguments = sys.argv[1:]
 
 videoFile = arguments[0]
@@ -25,12 +29,22 @@ mp_pose = mp.solutions.pose
 cap = cv2.VideoCapture(videoFile)
 squat_count = 0
 state_count = 0
-S_angle = 130
+# Knee angle a rep has to bend past to count, raised by the server for users who can't go as deep
+S_angle = float(arguments[2]) if len(arguments) > 2 else 130
 confident_threshold = 0.5
 smallestKneeAngle = S_angle
 # Store an array of array of features for squatRec[i][j] represent ith squat and jth keyth points of the smallest knee angle frame
 exerciseRec = [[]]
 i = 0
+# Landmarks in features_i for every frame, so the server can store the whole pose over time
+landmarkNames = ['left_shoulder', 'right_shoulder', 'left_hip', 'right_hip', 'left_knee', 'right_knee',
+                 'left_ankle', 'right_ankle', 'left_heel', 'right_heel', 'left_foot_index', 'right_foot_index']
+frameLandmarks = []
+# Frames where each rep starts, bottoms out and ends, parallel to exerciseRec
+# A rep starts from the most extended frame while standing before it
+repFrames = [{}]
+topAngle = 0
+topFrame = 0
 
 width = int(cap.get(cv2.CAP_PROP_FRAME_WIDTH))
 height = int(cap.get(cv2.CAP_PROP_FRAME_HEIGHT))
@@ -54,6 +68,9 @@ with mp_pose.Pose(min_detection_confidence=confident_threshold, min_tracking_con
         except:
             break
 
+        frameLandmarks.append(None)
+        frameIndex = len(frameLandmarks) - 1
+
         # Make detection
         results = pose.process(image)
 
@@ -72,6 +89,10 @@ with mp_pose.Pose(min_detection_confidence=confident_threshold, min_tracking_con
             lm = results.pose_landmarks.landmark
             pl = mp_pose.PoseLandmark
 
+            frameFeatures = [lm[pl.LEFT_SHOULDER.value], lm[pl.RIGHT_SHOULDER.value], lm[pl.LEFT_HIP.value], lm[pl.RIGHT_HIP.value], lm[pl.LEFT_KNEE.value], lm[pl.RIGHT_KNEE.value],
+                             lm[pl.LEFT_ANKLE.value], lm[pl.RIGHT_ANKLE.value], lm[pl.LEFT_HEEL.value], lm[pl.RIGHT_HEEL.value], lm[pl.LEFT_FOOT_INDEX.value], lm[pl.RIGHT_FOOT_INDEX.value]]
+            frameLandmarks[-1] = utils.landmark_dicts(frameFeatures)
+
             # Compute angle
             ar = [lm[pl.RIGHT_HIP.value].x, lm[pl.RIGHT_HIP.value].y]
             br = [lm[pl.RIGHT_KNEE.value].x, lm[pl.RIGHT_KNEE.value].y]
@@ -92,20 +113,27 @@ with mp_pose.Pose(min_detection_confidence=confident_threshold, min_tracking_con
             if (minKnee < S_angle):  # down position
                 if (state_count == 0):
                     state_count += 1
+                    repFrames[i]['start_frame'] = topFrame
 
                 if (minKnee < smallestKneeAngle):
                     smallestKneeAngle = minKnee
-                    features_i = [lm[pl.LEFT_SHOULDER.value], lm[pl.RIGHT_SHOULDER.value], lm[pl.LEFT_HIP.value], lm[pl.RIGHT_HIP.value], lm[pl.LEFT_KNEE.value], lm[pl.RIGHT_KNEE.value],
-                                  lm[pl.LEFT_ANKLE.value], lm[pl.RIGHT_ANKLE.value], lm[pl.LEFT_HEEL.value], lm[pl.RIGHT_HEEL.value], lm[pl.LEFT_FOOT_INDEX.value], lm[pl.RIGHT_FOOT_INDEX.value]]
-                    exerciseRec[i] = features_i
+                    exerciseRec[i] = frameFeatures
+                    repFrames[i]['bottom_frame'] = frameIndex
 
             elif (minKnee > S_angle):  # stand position
                 if (state_count == 1):   # Already down position
                     state_count = 0
                     squat_count += 1
+                    repFrames[i]['end_frame'] = frameIndex
+                    repFrames.append({})
+                    topAngle = minKnee
+                    topFrame = frameIndex
                     i += 1
                     smallestKneeAngle = minKnee
                     exerciseRec.append([])
+                elif (minKnee > topAngle):
+                    topAngle = minKnee
+                    topFrame = frameIndex
 
         except:
             pass
@@ -123,47 +151,12 @@ for exer_i_th in range(len(exerciseRec) - 1):  # last exercise is just uncounted
     inputPreData.append(df)
 
 ####### Pose Classification #######
-model = load_model('.ml/squatModel_lessClass.h5')
-feedback = []
-squatClass = [
-    {'name': 'Acceptable', 'description': 'Normal squat'},
-    {'name': 'Anterior Knee', 'description': 'Knee ahead of toes during exercise'},
-    {'name': 'Bent Over', 'description': 'Excessive flexing of hip and torso'},
-    {'name': 'Knee Valgus', 'description': 'Both knees pointing inside during exercise'},
-    {'name': 'Knee Varus', 'description': 'Both knees pointing outside during exercise'},
-    {'name': 'Half Squat', 'description': 'Insufficient squatting depth'},
-    {'name': 'Other', 'description': 'Probably standing most of the time'}
-]
-
-# Using https://www.raynersmale.com/blog/2014/1/31/optimising-your-squat
-# left_knee_angle, left_hip_angle, left_ankle_angle, right_knee_angle, right_hip_angle, right_ankle_angle
-# Data is obtained from assets/_squat_perfect.mp4
-perfectSquat = [84.87369318857579, 83.45166654986053, 153.37894731195783,
-                85.30513286618259, 83.7632826648259, 179.36188848969115]
-
-acceptedMultiplier = 0
-acceptedAngleTolerance = 7
-devisorTaken = 1.5
-for exer_i_th in range(len(inputPreData)):
-    # Preliminary check for Acceptability
-    largest = 0
-
-    # Take percenter diference form the perfect form
-    percentSumDiffer = 0
-    for i in range(6):
-        diff = abs(perfectSquat[i] - inputPreData[exer_i_th][i])
-
-        if (diff <= acceptedAngleTolerance):
-            percentDiff = 1
-        else:
-            # Percent diference from tolerated angle
-            percentDiff = 1 - (diff/(perfectSquat[i] - acceptedAngleTolerance))
-
-        percentSumDiffer += percentDiff
-
-    # Get average difference
-    percentSumDiffer = percentSumDiffer/6
+model = load_model(MODEL_PATH)
+predictions = []
 
+# The model's class order is SquatClass in cloud/server/src/analysis/squat.rs,
+# which also does the scoring from here
+for exer_i_th in range(len(inputPreData)):
     # Convert to np array
     exer_i = np.array(inputPreData[exer_i_th])
 
@@ -175,33 +168,13 @@ for exer_i_th in range(len(inputPreData)):
     exer_i = exer_i.reshape(-1, 21)
 
     # Run prediction on the sample data
-    predictions = model.predict(x=exer_i, batch_size=10, verbose=0)
-
-    ######## Feedback Generator #######
-
-    # Aggregate for Acceptable Class
-    # Take only 1 half weight from each class
-    percentSumDiffer = percentSumDiffer/devisorTaken
-    for class_i in range(1, 5):
-        weightTaken = percentSumDiffer*predictions[0][class_i]
-        predictions[0][class_i] -= weightTaken
-
-        predictions[0][0] += weightTaken
-
-    # largest = predictions.argmax()
-    # weightTaken = percentSumDiffer*predictions[0][largest]
-    # predictions[0][largest] -= weightTaken
-    # predictions[0][0] += weightTaken
-
-    largest = predictions.argmax()
-
-    fb_i = {
-        'ex_number': exer_i_th + 1,
-        'class': squatClass[largest]['name'],
-        'correction': squatClass[largest]['description'],
-        'confidence': predictions[0][largest] * 100
-    }
+    prediction = model.predict(x=exer_i, batch_size=10, verbose=0)
 
-    feedback.append(fb_i)
+    predictions.append({
+        'landmarks': utils.landmark_dicts(exerciseRec[exer_i_th]),
+        'probabilities': prediction[0].tolist(),
+        **repFrames[exer_i_th],
+    })
 
-print(json.dumps(feedback))
+utils.print_report(ANALYZER_NAME, ANALYZER_VERSION, MODEL_PATH, squat_predictions=predictions,
+                   landmarks={'names': landmarkNames, 'frames': frameLandmarks})
@@ -2,7 +2,7 @@ import { StyleSheet, Text, View, FlatList, RefreshControl, SafeAreaView, ScrollV
 import React, { useEffect, useState, useRef, useCallback } from 'react'
 import { Video } from 'expo-av'
 import { storage } from '../firebase'
-import { formatDateString, formatTimestampDate, getTimeTimestamp, capitalizeString, calculateSquatFeedback, calculateAccuracyPercentage, calculatePushupFeedback } from '../utils'
+import { formatDateString, formatTimestampDate, getTimeTimestamp, capitalizeString, calculateSquatFeedback, calculateAccuracyPercentage, calculatePushupFeedback, calculatePlankFeedback, frameToSeconds } from '../utils'
 import { Ionicons } from '@expo/vector-icons'; 
 import { database, auth } from '../firebase'
 import { getPushupClassColor, getSquatClassColor } from '../utils';
@@ -14,6 +14,9 @@ const WorkoutScreen = ({route, navigation}) => {
     const [videoUrl, setVideoUrl] = useState(null);
     const [reps, setReps] = useState([]);
     const [videoId, setVideoId] = useState("");
+    const [status, setStatus] = useState(null);
+    const [insight, setInsight] = useState(null);
+    const [hold, setHold] = useState(null);
     const [refreshing, setRefreshing] = useState(false);
 
     //Initial load of reps and video ID
@@ -32,8 +35,11 @@ const WorkoutScreen = ({route, navigation}) => {
         const workoutInfo = doc.data();
         setReps(workoutInfo.reps);
         setVideoId(workoutInfo.video_id);
+        setStatus(workoutInfo.status);
+        setInsight(workoutInfo.insight);
+        setHold(workoutInfo.hold);
 
-        if (workoutInfo.video_id !== videoId) {
+        if (workoutInfo.video_id && workoutInfo.video_id !== videoId) {
           setVideoId(workoutInfo.video_id);
           var videoRef = storage.ref(`videos/${workoutInfo.video_id}`);
           videoRef.getDownloadURL()
@@ -73,12 +79,18 @@ const WorkoutScreen = ({route, navigation}) => {
                 isLooping
             />
             }
-            {!videoUrl && 
+            {!videoUrl && status?.state !== 'failed' &&
             <View style={styles.videoLoading}>
-              <Text>Video pending upload from server...</Text>
+              <Text>{status?.state === 'recording' ? 'Workout is still being recorded...' : 'Video pending upload from server...'}</Text>
+            </View>}
+            {status?.state === 'failed' &&
+            <View style={styles.videoLoading}>
+              <Text>{`Workout could not be processed: ${status.reason}`}</Text>
             </View>}
             <View style={styles.summary}>
-              <Text style={styles.summaryHeaderText}>{type == 'squat' ? calculateSquatFeedback(reps) : calculatePushupFeedback(reps)}</Text>
+              <Text style={styles.summaryHeaderText}>{type == 'plank' ? calculatePlankFeedback(hold) : type == 'squat' ? calculateSquatFeedback(reps) : calculatePushupFeedback(reps)}</Text>
+              {insight &&
+              <Text style={styles.insightText}>{insight.message}</Text>}
             </View>
               <View style={styles.repListContainer}>
                 {reps &&
@@ -110,6 +122,25 @@ const WorkoutScreen = ({route, navigation}) => {
                   )
                 })
                 }
+                {hold &&
+                hold.faults.map((fault, index) => {
+                  return (
+                    <View style={styles.listItem}
+                      key={index}
+                    >
+                    <View style={styles.listHeader}>
+                      <View style={styles.listItemNumberCircle}>
+                        <Text style={styles.listItemNumberText}>{index + 1}</Text>
+                      </View>
+                      <View style={styles.listItemText}>
+                        <Text style={{fontSize: 18}}>{fault.kind === 'hips_sagged' ? 'Hips sagged' : 'Hips piked'}</Text>
+                      </View>
+                    </View>
+                    <Text style={styles.correctionText}>{`From ${frameToSeconds(fault.start_frame)}s to ${frameToSeconds(fault.end_frame)}s`}</Text>
+                  </View>
+                  )
+                })
+                }
               </View>
             </View>
           </ScrollView>
@@ -162,6 +193,11 @@ const styles = StyleSheet.create({
       fontSize: 22,
       marginTop: 10,
     },
+    insightText: {
+      color: 'white',
+      fontSize: 16,
+      marginTop: 6,
+    },
     repListContainer: {
       flex: 1,
       flexGrow: 1,
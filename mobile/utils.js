@@ -48,6 +48,16 @@ export function calculatePushupFeedback(reps) {
   return `Most common correction: ${mostCommonClass}`
 }
 
+export function calculatePlankFeedback(hold) {
+  if (!hold) return '';
+  return `Held for ${hold.hold_secs.toFixed(1)}s, ${hold.aligned_secs.toFixed(1)}s of it in good alignment`
+}
+
+// Videos from the server are 30 fps
+export function frameToSeconds(frame) {
+  return (frame / 30).toFixed(1);
+}
+
 // Taken from: https://stackoverflow.com/questions/1053843/get-the-element-with-the-highest-occurrence-in-an-array
 function mode(array)
 {